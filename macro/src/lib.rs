@@ -129,3 +129,92 @@ pub fn derive_has_origin(input: TokenStream) -> TokenStream {
 }
 
 
+
+#[proc_macro_derive(TrackableResource, attributes(tracked))]
+pub fn derive_trackable_resource(input: TokenStream) -> TokenStream {
+    let ast = parse_macro_input!(input as DeriveInput);
+
+    match trackable_resource_impl(ast) {
+        Ok(tokens) => TokenStream::from(tokens),
+        Err(err) => TokenStream::from(err.to_compile_error()),
+    }
+}
+
+fn trackable_resource_impl(ast: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let struct_name = &ast.ident;
+    let mut key = None;
+    let mut items = vec![];
+
+    for attribute in ast.attrs.iter().filter(|x| x.path().is_ident("tracked")) {
+        attribute.parse_nested_meta(|meta| {
+            let value = meta.value()?;
+            if meta.path.is_ident("key") {
+                key = Some(value.parse::<syn::LitStr>()?);
+            } else if meta.path.is_ident("version") {
+                let version: syn::LitInt = value.parse()?;
+                items.push(quote!(const VERSION: u32 = #version;));
+            } else if meta.path.is_ident("migrate") {
+                let path: syn::Path = value.parse()?;
+                items.push(quote!(
+                    fn migrate(
+                        from_version: u32,
+                        value: nice_bevy_utils::serde_json::Value,
+                    ) -> Result<nice_bevy_utils::serde_json::Value, nice_bevy_utils::tracked_resource::migration::MigrationError> {
+                        #path(from_version, value)
+                    }
+                ));
+            } else if meta.path.is_ident("on_loaded") {
+                let path: syn::Path = value.parse()?;
+                items.push(quote!(
+                    fn on_loaded(&mut self) {
+                        #path(self)
+                    }
+                ));
+            } else if meta.path.is_ident("save_policy") {
+                let expr: syn::Expr = value.parse()?;
+                items.push(quote!(const SAVE_POLICY: nice_bevy_utils::tracked_resource::save_policy::SavePolicy = #expr;));
+            } else if meta.path.is_ident("save_in_background") {
+                let expr: syn::LitBool = value.parse()?;
+                items.push(quote!(const SAVE_IN_BACKGROUND: bool = #expr;));
+            } else if meta.path.is_ident("format") {
+                let expr: syn::Expr = value.parse()?;
+                items.push(quote!(const FORMAT: nice_bevy_utils::tracked_resource::format::Format = #expr;));
+            } else if meta.path.is_ident("compression") {
+                let expr: syn::Expr = value.parse()?;
+                items.push(quote!(const COMPRESSION: nice_bevy_utils::tracked_resource::format::Compression = #expr;));
+            } else if meta.path.is_ident("lenient") {
+                let expr: syn::LitBool = value.parse()?;
+                items.push(quote!(const LENIENT: bool = #expr;));
+            } else if meta.path.is_ident("failure_policy") {
+                let expr: syn::Expr = value.parse()?;
+                items.push(quote!(const FAILURE_POLICY: nice_bevy_utils::tracked_resource::error::FailurePolicy = #expr;));
+            } else if meta.path.is_ident("conflict_strategy") {
+                let expr: syn::Expr = value.parse()?;
+                items.push(quote!(const CONFLICT_STRATEGY: nice_bevy_utils::tracked_resource::sync::ConflictStrategy<Self> = #expr;));
+            } else if meta.path.is_ident("protection") {
+                let expr: syn::Expr = value.parse()?;
+                items.push(quote!(const PROTECTION: nice_bevy_utils::tracked_resource::protection::Protection = #expr;));
+            } else {
+                return Err(meta.error("unknown `tracked` attribute"));
+            }
+            Ok(())
+        })?;
+    }
+
+    // Default to the type path so that keys are unique without having to think about them
+    let key = match key {
+        Some(key) => quote!(#key),
+        None => quote!(concat!(module_path!(), "::", stringify!(#struct_name))),
+    };
+
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics nice_bevy_utils::TrackableResource for #struct_name #ty_generics #where_clause
+        {
+            const KEY: &'static str = #key;
+
+            #(#items)*
+        }
+    })
+}
@@ -0,0 +1,267 @@
+use bevy::prelude::*;
+use futures_core::Stream;
+use std::collections::VecDeque;
+use std::fmt::Display;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::task::{Context, Poll, Waker};
+
+pub(crate) struct AsyncEventReaderPlugin<T: Event + Clone> {
+    capacity: usize,
+    phantom: PhantomData<T>,
+}
+
+impl<T: Event + Clone> AsyncEventReaderPlugin<T> {
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "Async event reader capacity must not be zero");
+        Self {
+            capacity,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: Event + Clone> Plugin for AsyncEventReaderPlugin<T> {
+    fn build(&self, app: &mut App) {
+        app.add_event::<T>()
+            .add_systems(Last, forward_events::<T>)
+            .insert_resource(AsyncEventSubscriptions::<T> {
+                subscriptions: vec![],
+                capacity: self.capacity,
+            });
+    }
+}
+
+fn forward_events<T: Event + Clone>(
+    mut reader: EventReader<T>,
+    mut subscriptions: ResMut<AsyncEventSubscriptions<T>>,
+) {
+    // Readers that have been dropped no longer need events
+    subscriptions
+        .subscriptions
+        .retain(|x| Arc::strong_count(x) > 1);
+
+    let events: Vec<T> = reader.read().cloned().collect();
+    if events.is_empty() {
+        return;
+    }
+    for subscription in subscriptions.subscriptions.iter() {
+        subscription
+            .lock()
+            .push(events.iter().cloned(), subscriptions.capacity);
+    }
+}
+
+/// Creates [`AsyncEventReader`]s which receive every event of type `T` sent in the app
+#[derive(Resource)]
+pub struct AsyncEventSubscriptions<T: Event + Clone> {
+    subscriptions: Vec<Arc<Subscription<T>>>,
+    capacity: usize,
+}
+
+impl<T: Event + Clone> AsyncEventSubscriptions<T> {
+    /// Receive every event sent from now on
+    pub fn subscribe(&mut self) -> AsyncEventReader<T> {
+        let subscription = Arc::new(Subscription {
+            state: Mutex::new(SubscriptionState {
+                events: VecDeque::new(),
+                lagged: 0,
+                waker: None,
+                closed: false,
+            }),
+        });
+        self.subscriptions.push(subscription.clone());
+        AsyncEventReader(subscription)
+    }
+
+    /// The number of readers that have not been dropped
+    pub fn subscriber_count(&self) -> usize {
+        self.subscriptions
+            .iter()
+            .filter(|x| Arc::strong_count(x) > 1)
+            .count()
+    }
+}
+
+impl<T: Event + Clone> Drop for AsyncEventSubscriptions<T> {
+    fn drop(&mut self) {
+        // End every stream so that tasks waiting on them finish
+        for subscription in self.subscriptions.iter() {
+            let mut state = subscription.lock();
+            state.closed = true;
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Subscription<T> {
+    state: Mutex<SubscriptionState<T>>,
+}
+
+#[derive(Debug)]
+struct SubscriptionState<T> {
+    events: VecDeque<T>,
+    /// Events dropped since the reader last received one
+    lagged: u64,
+    waker: Option<Waker>,
+    closed: bool,
+}
+
+impl<T> Subscription<T> {
+    fn lock(&self) -> MutexGuard<'_, SubscriptionState<T>> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<T> SubscriptionState<T> {
+    fn push(&mut self, events: impl Iterator<Item = T>, capacity: usize) {
+        self.events.extend(events);
+        if self.events.len() > capacity {
+            let dropped = self.events.len() - capacity;
+            self.events.drain(..dropped);
+            self.lagged += dropped as u64;
+        }
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+
+    /// Take the next event, or the number of events missed if the reader fell behind
+    fn pop(&mut self) -> Option<Result<T, Lagged>> {
+        if self.lagged > 0 {
+            return Some(Err(Lagged(std::mem::take(&mut self.lagged))));
+        }
+        self.events.pop_front().map(Ok)
+    }
+}
+
+/// The reader fell behind and this many of the oldest events were dropped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Lagged(pub u64);
+
+impl Display for Lagged {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Reader fell behind and missed {} events", self.0)
+    }
+}
+
+impl std::error::Error for Lagged {}
+
+/// A stream of the events of type `T` sent in the app, for use in async tasks.
+/// Create one with [`AsyncEventSubscriptions::subscribe`].
+///
+/// Each reader keeps a limited number of events. If it falls behind, the oldest events are dropped and it receives a [`Lagged`] error.
+/// The stream ends when the app is dropped.
+#[derive(Debug)]
+pub struct AsyncEventReader<T: Event>(Arc<Subscription<T>>);
+
+impl<T: Event> AsyncEventReader<T> {
+    /// Take the next event without waiting, if there is one
+    pub fn try_recv(&mut self) -> Option<Result<T, Lagged>> {
+        self.0.lock().pop()
+    }
+}
+
+impl<T: Event> Stream for AsyncEventReader<T> {
+    type Item = Result<T, Lagged>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        // Hold the lock until the waker is stored so that events pushed in between still wake the task
+        let mut state = self.0.lock();
+        if let Some(next) = state.pop() {
+            return Poll::Ready(Some(next));
+        }
+        if state.closed {
+            return Poll::Ready(None);
+        }
+        state.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_lite::{future::block_on, StreamExt};
+
+    use crate::CanRegisterAsyncEvent;
+
+    use super::*;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Event)]
+    struct Score(u32);
+
+    fn subscribe(app: &mut App) -> AsyncEventReader<Score> {
+        app.world_mut()
+            .resource_mut::<AsyncEventSubscriptions<Score>>()
+            .subscribe()
+    }
+
+    fn send(app: &mut App, scores: impl IntoIterator<Item = u32>) {
+        for score in scores {
+            app.world_mut().send_event(Score(score));
+        }
+        app.update();
+    }
+
+    #[test]
+    pub fn test_events_are_forwarded_to_every_reader() {
+        let mut app = App::new();
+        app.register_async_event_reader::<Score>(4);
+        let mut first = subscribe(&mut app);
+        let mut second = subscribe(&mut app);
+
+        send(&mut app, [1, 2]);
+        assert_eq!(block_on(first.next()), Some(Ok(Score(1))));
+        assert_eq!(block_on(first.next()), Some(Ok(Score(2))));
+        assert_eq!(block_on(second.next()), Some(Ok(Score(1))));
+
+        drop(first);
+        send(&mut app, [3]);
+        let subscriptions = app.world().resource::<AsyncEventSubscriptions<Score>>();
+        assert_eq!(subscriptions.subscriber_count(), 1);
+        assert_eq!(subscriptions.subscriptions.len(), 1);
+
+        assert_eq!(block_on(second.next()), Some(Ok(Score(2))));
+        assert_eq!(block_on(second.next()), Some(Ok(Score(3))));
+        assert_eq!(second.try_recv(), None);
+
+        drop(app);
+        assert_eq!(block_on(second.next()), None);
+    }
+
+    #[test]
+    pub fn test_waiting_reader_is_woken() {
+        let mut app = App::new();
+        app.register_async_event_reader::<Score>(4);
+        let mut reader = subscribe(&mut app);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            while let Some(score) = block_on(reader.next()) {
+                sender.send(score).unwrap();
+            }
+        });
+
+        // Each event is only sent once the last one arrived, so a missed wakeup would never be recovered
+        for score in 0..200 {
+            send(&mut app, [score]);
+            let received = receiver.recv_timeout(std::time::Duration::from_secs(5));
+            assert_eq!(received, Ok(Ok(Score(score))));
+        }
+    }
+
+    #[test]
+    pub fn test_slow_reader_lags() {
+        let mut app = App::new();
+        app.register_async_event_reader::<Score>(2);
+        let mut reader = subscribe(&mut app);
+
+        send(&mut app, [1, 2, 3]);
+        send(&mut app, [4]);
+        let received: Vec<_> = block_on((&mut reader).take(3).collect());
+        assert_eq!(received, [Err(Lagged(2)), Ok(Score(3)), Ok(Score(4))]);
+    }
+}
@@ -1,12 +1,68 @@
 use bevy::{ecs::system::SystemParam, prelude::*};
+use std::collections::VecDeque;
 use std::marker::PhantomData;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::*;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
 
-pub(crate) struct AsyncEventPlugin<T: Event>(PhantomData<T>);
+/// How async events are queued before they are sent in the app
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct AsyncEventOptions {
+    /// The most events that can be queued, or `None` for no limit
+    pub capacity: Option<usize>,
+    /// What to do when an event is sent while the queue is full
+    pub overflow: OverflowPolicy,
+    /// The most events that are sent in the app each frame, or `None` for no limit. Must not be zero.
+    /// Leftover events are sent in the next frame.
+    pub max_per_frame: Option<usize>,
+}
+
+impl AsyncEventOptions {
+    /// Queue at most `capacity` events. Panics if `capacity` is zero.
+    pub fn bounded(capacity: usize, overflow: OverflowPolicy) -> Self {
+        assert!(capacity > 0, "Async event capacity must not be zero");
+        Self {
+            capacity: Some(capacity),
+            overflow,
+            max_per_frame: None,
+        }
+    }
+
+    /// Send at most `max_per_frame` events in the app each frame. Panics if `max_per_frame` is zero.
+    pub fn with_max_per_frame(mut self, max_per_frame: usize) -> Self {
+        assert!(max_per_frame > 0, "Async events per frame must not be zero");
+        self.max_per_frame = Some(max_per_frame);
+        self
+    }
+}
+
+/// What to do when an async event is sent while the queue is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowPolicy {
+    /// Wait until there is room.
+    /// The queue is only emptied by a system in the app's `Update` schedule, so don't send from anything the app update waits on,
+    /// such as a system or the thread that runs the app, or it will wait forever.
+    #[default]
+    Block,
+    /// Discard the event being sent
+    DropNewest,
+    /// Discard the event that has been queued longest
+    DropOldest,
+    /// Replace the most recently queued event with the event being sent
+    CoalesceLatest,
+}
+
+pub(crate) struct AsyncEventPlugin<T: Event>(AsyncEventOptions, PhantomData<T>);
 
 impl<T: Event> Default for AsyncEventPlugin<T> {
     fn default() -> Self {
-        Self(Default::default())
+        Self::new(AsyncEventOptions::default())
+    }
+}
+
+impl<T: Event> AsyncEventPlugin<T> {
+    pub fn new(options: AsyncEventOptions) -> Self {
+        Self(options, PhantomData)
     }
 }
 
@@ -14,20 +70,89 @@ impl<T: Event> Plugin for AsyncEventPlugin<T> {
     fn build(&self, app: &mut App) {
         app.add_event::<T>()
             .add_systems(Update, poll_events::<T>)
-            .init_non_send_resource::<AsyncEventResource<T>>();
+            .insert_resource(AsyncEventResource::<T>::new(self.0));
+    }
+}
+
+fn poll_events<T: Event>(channels: Res<AsyncEventResource<T>>, mut writer: EventWriter<T>) {
+    let channel = &channels.channel;
+    let mut queue = channel.lock();
+    let count = channel
+        .options
+        .max_per_frame
+        .map_or(queue.len(), |max| max.min(queue.len()));
+    if count == 0 {
+        return;
     }
+
+    writer.send_batch(queue.drain(..count));
+    drop(queue);
+    channel.not_full.notify_all();
+}
+
+/// The queue shared by the writers and the app
+#[derive(Debug)]
+struct Channel<T> {
+    queue: Mutex<VecDeque<T>>,
+    not_full: Condvar,
+    options: AsyncEventOptions,
+    /// The app has been dropped so events can no longer be received
+    closed: AtomicBool,
 }
 
-fn poll_events<T: Event>(channels: NonSend<AsyncEventResource<T>>, mut writer: EventWriter<T>) {
-    while let Ok(ev) = channels.receiver.try_recv() {
-        writer.send(ev);
+impl<T> Channel<T> {
+    fn lock(&self) -> MutexGuard<'_, VecDeque<T>> {
+        self.queue.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn send(&self, event: T) -> Result<(), SendError<T>> {
+        let mut queue = self.lock();
+        loop {
+            if self.closed.load(Ordering::Acquire) {
+                return Err(SendError(event));
+            }
+            match self.options.capacity {
+                Some(capacity) if queue.len() >= capacity => match self.options.overflow {
+                    OverflowPolicy::Block => {
+                        queue = self
+                            .not_full
+                            .wait(queue)
+                            .unwrap_or_else(PoisonError::into_inner);
+                    }
+                    OverflowPolicy::DropNewest => return Ok(()),
+                    OverflowPolicy::DropOldest => {
+                        queue.pop_front();
+                        queue.push_back(event);
+                        return Ok(());
+                    }
+                    OverflowPolicy::CoalesceLatest => {
+                        if let Some(latest) = queue.back_mut() {
+                            *latest = event;
+                        }
+                        return Ok(());
+                    }
+                },
+                _ => {
+                    queue.push_back(event);
+                    return Ok(());
+                }
+            }
+        }
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct AsyncEventWriter<T: Event>(Sender<T>);
+#[derive(Debug)]
+pub struct AsyncEventWriter<T: Event>(Arc<Channel<T>>);
+
+impl<T: Event> Clone for AsyncEventWriter<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
 
 impl<T: Event> AsyncEventWriter<T> {
+    /// Queue an event to be sent in the app.
+    /// If the queue is full this waits or discards an event, depending on the [`OverflowPolicy`].
     pub fn send(&self, event: T) -> Result<(), SendError<T>> {
         self.0.send(event)
     }
@@ -42,25 +167,33 @@ impl<T: Event> AsyncEventWriter<T> {
     }
 }
 
+// Reads the channel like `Option<Res<AsyncEventResource<T>>>`, so systems with writers can run on any thread and in parallel
 unsafe impl<T: Event> SystemParam for AsyncEventWriter<T> {
-    type State = ();
+    type State = bevy::ecs::component::ComponentId;
 
     type Item<'world, 'state> = Self;
 
     fn init_state(
-        _world: &mut World,
-        _system_meta: &mut bevy::ecs::system::SystemMeta,
+        world: &mut World,
+        system_meta: &mut bevy::ecs::system::SystemMeta,
     ) -> Self::State {
+        Option::<Res<AsyncEventResource<T>>>::init_state(world, system_meta)
     }
 
     unsafe fn get_param<'world, 'state>(
-        _: &'state mut Self::State,
-        _: &bevy::ecs::system::SystemMeta,
+        state: &'state mut Self::State,
+        system_meta: &bevy::ecs::system::SystemMeta,
         world: bevy::ecs::world::unsafe_world_cell::UnsafeWorldCell<'world>,
-        _: bevy::ecs::component::Tick,
+        change_tick: bevy::ecs::component::Tick,
     ) -> Self::Item<'world, 'state> {
-        match world.get_non_send_resource::<AsyncEventResource<T>>() {
-            Some(resource) => Self(resource.sender.clone()),
+        let resource = Option::<Res<AsyncEventResource<T>>>::get_param(
+            state,
+            system_meta,
+            world,
+            change_tick,
+        );
+        match resource {
+            Some(resource) => Self(resource.channel.clone()),
             None => panic!(
                 "Event {} is not registered as an async event",
                 std::any::type_name::<T>()
@@ -71,13 +204,138 @@ unsafe impl<T: Event> SystemParam for AsyncEventWriter<T> {
 
 #[derive(Resource)]
 struct AsyncEventResource<T: Event> {
-    sender: Sender<T>,
-    receiver: Receiver<T>,
+    channel: Arc<Channel<T>>,
 }
 
-impl<T: Event> Default for AsyncEventResource<T> {
-    fn default() -> Self {
-        let (sender, receiver) = std::sync::mpsc::channel();
-        Self { sender, receiver }
+impl<T: Event> AsyncEventResource<T> {
+    fn new(options: AsyncEventOptions) -> Self {
+        Self {
+            channel: Arc::new(Channel {
+                queue: Default::default(),
+                not_full: Condvar::new(),
+                options,
+                closed: AtomicBool::new(false),
+            }),
+        }
+    }
+}
+
+impl<T: Event> Drop for AsyncEventResource<T> {
+    fn drop(&mut self) {
+        // Wake any blocked writers so that they fail rather than wait forever
+        let _queue = self.channel.lock();
+        self.channel.closed.store(true, Ordering::Release);
+        self.channel.not_full.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use crate::CanRegisterAsyncEvent;
+
+    use super::*;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Event)]
+    struct Progress(u32);
+
+    fn received(app: &mut App) -> Vec<u32> {
+        app.update();
+        let events = app.world().resource::<Events<Progress>>();
+        events
+            .get_reader()
+            .read(events)
+            .map(|x| x.0)
+            .collect()
+    }
+
+    fn send_all(app: &mut App, count: u32) {
+        let writer = app
+            .world_mut()
+            .run_system_once(|writer: AsyncEventWriter<Progress>| writer);
+        for x in 1..=count {
+            writer.send(Progress(x)).unwrap();
+        }
+    }
+
+    fn app(options: AsyncEventOptions) -> App {
+        let mut app = App::new();
+        app.register_async_event_with_options::<Progress>(options);
+        app
+    }
+
+    #[test]
+    pub fn test_overflow_policies() {
+        for (policy, expected) in [
+            (OverflowPolicy::DropNewest, vec![1, 2, 3]),
+            (OverflowPolicy::DropOldest, vec![4, 5, 6]),
+            (OverflowPolicy::CoalesceLatest, vec![1, 2, 6]),
+        ] {
+            let mut app = app(AsyncEventOptions::bounded(3, policy));
+            send_all(&mut app, 6);
+            assert_eq!(received(&mut app), expected, "{policy:?}");
+        }
+    }
+
+    #[test]
+    pub fn test_blocked_writer_waits_for_room() {
+        let mut app = app(AsyncEventOptions::bounded(2, OverflowPolicy::Block));
+        send_all(&mut app, 2);
+        let writer = app
+            .world_mut()
+            .run_system_once(|writer: AsyncEventWriter<Progress>| writer);
+        let thread = std::thread::spawn(move || writer.send(Progress(3)));
+
+        assert_eq!(received(&mut app), [1, 2]);
+        thread.join().unwrap().unwrap();
+        let mut events = received(&mut app);
+        events.retain(|x| *x == 3);
+        assert_eq!(events, [3]);
+
+        // Blocked writers fail when the app is dropped
+        send_all(&mut app, 2);
+        let writer = app
+            .world_mut()
+            .run_system_once(|writer: AsyncEventWriter<Progress>| writer);
+        let thread = std::thread::spawn(move || writer.send(Progress(3)));
+        drop(app);
+        assert!(thread.join().unwrap().is_err());
+    }
+
+    #[test]
+    pub fn test_systems_can_run_on_any_thread() {
+        let mut app = app(AsyncEventOptions::default());
+        let world = app.world_mut();
+
+        let mut poll = IntoSystem::into_system(poll_events::<Progress>);
+        poll.initialize(world);
+        let mut write = IntoSystem::into_system(|writer: AsyncEventWriter<Progress>| {
+            writer.send_or_panic(Progress(1));
+        });
+        write.initialize(world);
+
+        assert!(poll.is_send());
+        assert!(write.is_send());
+        assert!(!write.component_access().has_any_write());
+        write.run((), world);
+        assert_eq!(received(&mut app), [1]);
+    }
+
+    #[test]
+    pub fn test_max_per_frame() {
+        let mut app = app(AsyncEventOptions::default().with_max_per_frame(2));
+        send_all(&mut app, 5);
+
+        assert_eq!(received(&mut app), [1, 2]);
+        let mut events = received(&mut app);
+        events.retain(|x| *x > 2);
+        assert_eq!(events, [3, 4]);
+    }
+
+    #[test]
+    #[should_panic(expected = "must not be zero")]
+    pub fn test_zero_max_per_frame_panics() {
+        AsyncEventOptions::default().with_max_per_frame(0);
     }
 }
@@ -0,0 +1,225 @@
+use bevy::prelude::*;
+use std::fmt::Display;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::task::{Context, Poll, Waker};
+
+type Job = Box<dyn FnOnce(&mut World) + Send>;
+
+pub(crate) struct AsyncWorldPlugin;
+
+impl Plugin for AsyncWorldPlugin {
+    fn build(&self, app: &mut App) {
+        let world = AsyncWorld(Default::default());
+        app.insert_resource(AsyncWorldQueue(world.0.clone()))
+            .insert_resource(world)
+            .add_systems(Update, run_jobs);
+    }
+}
+
+fn run_jobs(world: &mut World) {
+    // Jobs submitted while these run wait for the next frame
+    let jobs = std::mem::take(&mut world.resource::<AsyncWorldQueue>().0.lock().jobs);
+    for job in jobs {
+        job(world);
+    }
+}
+
+#[derive(Default)]
+struct Queue {
+    jobs: Vec<Job>,
+    closed: bool,
+}
+
+#[derive(Default)]
+struct SharedQueue(Mutex<Queue>);
+
+impl SharedQueue {
+    fn lock(&self) -> MutexGuard<'_, Queue> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Owns the queue so that outstanding requests fail when the app is dropped
+#[derive(Resource)]
+struct AsyncWorldQueue(Arc<SharedQueue>);
+
+impl Drop for AsyncWorldQueue {
+    fn drop(&mut self) {
+        let jobs = {
+            let mut queue = self.0.lock();
+            queue.closed = true;
+            std::mem::take(&mut queue.jobs)
+        };
+        // Dropping the jobs resolves their futures with an error
+        drop(jobs);
+    }
+}
+
+/// A handle that async tasks can use to run code against the [`World`] and wait for the result.
+/// Clone it out of the resource and move it into the task.
+///
+/// Requests run in [`Update`], in the order they were made.
+#[derive(Resource, Clone)]
+pub struct AsyncWorld(Arc<SharedQueue>);
+
+impl AsyncWorld {
+    /// Run a closure against the world in the next update
+    pub fn run<R: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut World) -> R + Send + 'static,
+    ) -> WorldResponse<R> {
+        let response = Arc::new(Mutex::new(ResponseState {
+            result: None,
+            waker: None,
+            done: false,
+        }));
+        let responder = Responder(response.clone());
+        let job: Job = Box::new(move |world| responder.respond(f(world)));
+
+        let mut queue = self.0.lock();
+        if queue.closed {
+            drop(queue);
+            drop(job);
+        } else {
+            queue.jobs.push(job);
+        }
+        WorldResponse(response)
+    }
+
+    /// Run a typed request against the world in the next update
+    pub fn request<Q: WorldRequest>(&self, request: Q) -> WorldResponse<Q::Response> {
+        self.run(move |world| request.run(world))
+    }
+}
+
+/// A request that can be made with [`AsyncWorld::request`]
+pub trait WorldRequest: Send + 'static {
+    type Response: Send + 'static;
+
+    fn run(self, world: &mut World) -> Self::Response;
+}
+
+/// The app was dropped before a request was answered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AppClosed;
+
+impl Display for AppClosed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "The app shut down before the request was answered")
+    }
+}
+
+impl std::error::Error for AppClosed {}
+
+struct ResponseState<R> {
+    result: Option<R>,
+    waker: Option<Waker>,
+    /// The responder has been dropped, after answering or not
+    done: bool,
+}
+
+type SharedResponse<R> = Arc<Mutex<ResponseState<R>>>;
+
+fn lock<R>(response: &SharedResponse<R>) -> MutexGuard<'_, ResponseState<R>> {
+    response.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Answers a request. If it is dropped without answering, the request fails with [`AppClosed`].
+struct Responder<R>(SharedResponse<R>);
+
+impl<R> Responder<R> {
+    fn respond(self, result: R) {
+        lock(&self.0).result = Some(result);
+    }
+}
+
+impl<R> Drop for Responder<R> {
+    fn drop(&mut self) {
+        let mut state = lock(&self.0);
+        state.done = true;
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// The result of a request made with [`AsyncWorld`]
+pub struct WorldResponse<R>(SharedResponse<R>);
+
+impl<R> Future for WorldResponse<R> {
+    type Output = Result<R, AppClosed>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = lock(&self.0);
+        if let Some(result) = state.result.take() {
+            return Poll::Ready(Ok(result));
+        }
+        if state.done {
+            return Poll::Ready(Err(AppClosed));
+        }
+        state.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_lite::future::block_on;
+
+    use crate::CanUseAsyncWorld;
+
+    use super::*;
+
+    #[derive(Debug, Resource)]
+    struct Level(u32);
+
+    struct NextLevel;
+
+    impl WorldRequest for NextLevel {
+        type Response = u32;
+
+        fn run(self, world: &mut World) -> u32 {
+            let mut level = world.resource_mut::<Level>();
+            level.0 += 1;
+            level.0
+        }
+    }
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.insert_resource(Level(1));
+        app.add_async_world();
+        app
+    }
+
+    #[test]
+    pub fn test_requests_are_answered() {
+        let mut app = app();
+        let world = app.world().resource::<AsyncWorld>().clone();
+        let task = std::thread::spawn(move || {
+            let level = block_on(world.run(|world| world.resource::<Level>().0));
+            let next = block_on(world.request(NextLevel));
+            (level, next)
+        });
+
+        while !task.is_finished() {
+            app.update();
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        assert_eq!(task.join().unwrap(), (Ok(1), Ok(2)));
+        assert_eq!(app.world().resource::<Level>().0, 2);
+    }
+
+    #[test]
+    pub fn test_requests_fail_when_the_app_closes() {
+        let app = app();
+        let world = app.world().resource::<AsyncWorld>().clone();
+        let pending = world.request(NextLevel);
+
+        drop(app);
+        assert_eq!(block_on(pending), Err(AppClosed));
+        assert_eq!(block_on(world.request(NextLevel)), Err(AppClosed));
+    }
+}
@@ -1,10 +1,24 @@
+use std::borrow::Cow;
+
+use async_event_writer::AsyncEventOptions;
 use bevy::prelude::{App, Event};
+use tracked_resource::{
+    entities::{TrackableComponent, TrackedEntityMarker},
+    error::{FailurePolicy, ValidationError},
+    format::{Compression, Format},
+    migration::MigrationError,
+    protection::Protection,
+    reflect::ReflectTrackableResource,
+    save_policy::SavePolicy,
+    sync::ConflictStrategy,
+};
 
 pub mod any_event_writer;
 pub mod any_res_mut;
+pub mod async_event_reader;
 pub mod async_event_writer;
+pub mod async_world;
 pub mod asynchronous;
-#[cfg(feature = "bevy_pkv")]
 pub mod tracked_resource;
 pub mod window_size;
 
@@ -12,44 +26,180 @@ pub mod insets;
 pub mod layout;
 pub mod click;
 
+pub use serde_json;
+
+#[cfg(any(feature = "derive", test))]
+pub use nice_bevy_utils_macro::TrackableResource;
+
+/// A resource which is loaded from and saved to storage.
+///
+/// With the `derive` feature this can be derived, configured with a `#[tracked(...)]` attribute.
+/// The key defaults to the type path.
+/// ```ignore
+/// #[derive(Resource, Clone, Default, Serialize, Deserialize, TrackableResource)]
+/// #[tracked(key = "settings", version = 2, migrate = migrate_settings, on_loaded = Settings::apply)]
+/// struct Settings { volume: f32 }
+/// ```
 pub trait TrackableResource:
     bevy::prelude::Resource + serde::Serialize + serde::de::DeserializeOwned + Clone
 {
     const KEY: &'static str;
 
+    /// The version of the stored shape of this resource.
+    /// Increase this when a change would stop old values from deserializing and handle the old version in `migrate`.
+    const VERSION: u32 = 0;
+
+    /// Upgrade a stored value from `from_version` to `from_version + 1`.
+    /// This is called repeatedly until the value reaches `VERSION`.
+    fn migrate(
+        from_version: u32,
+        _value: serde_json::Value,
+    ) -> Result<serde_json::Value, MigrationError> {
+        Err(MigrationError::Missing { from_version })
+    }
+
+    /// When changes to this resource are written to storage
+    const SAVE_POLICY: SavePolicy = SavePolicy::Immediate;
+
+    /// Serialize the resource on the main thread but write it to storage on the `IoTaskPool`.
+    /// Useful for large resources. Has no effect on wasm.
+    const SAVE_IN_BACKGROUND: bool = false;
+
+    /// What to do when this resource cannot be loaded or saved
+    const FAILURE_POLICY: FailurePolicy = FailurePolicy::Log;
+
+    /// How this resource is encoded in storage
+    const FORMAT: Format = Format::Json;
+
+    /// Whether stored values of this resource are compressed
+    const COMPRESSION: Compression = Compression::None;
+
+    /// Sign or encrypt stored values so that players cannot easily edit them
+    const PROTECTION: Protection = Protection::None;
+
+    /// When a stored value cannot be read, keep the fields that can be and take the rest from the default value,
+    /// instead of replacing the whole value. The replaced fields are logged.
+    const LENIENT: bool = false;
+
+    /// How to resolve changes made on two devices when this resource is synced
+    const CONFLICT_STRATEGY: ConflictStrategy<Self> = ConflictStrategy::LastWriteWins;
+
     /// Optional function that is called when the resource is loaded
     fn on_loaded(&mut self) {}
+
+    /// Check that a value is valid.
+    /// Loaded values that fail are repaired, and invalid values are not saved.
+    /// Failures are sent as [`tracked_resource::error::TrackedResourceError`]s.
+    fn validate(&self) -> Result<(), ValidationError> {
+        Ok(())
+    }
+
+    /// Fix a loaded value that failed validation.
+    /// If it is still invalid afterwards the backup or default value is used instead.
+    fn repair(&mut self) {}
+
+    /// The value to store, for example with transient fields stripped. The resource itself is not changed.
+    fn before_save(&self) -> Cow<'_, Self> {
+        Cow::Borrowed(self)
+    }
 }
 
+/// Add tracked resources which are loaded from and saved to the [`tracked_resource::storage::TrackedStorage`]
 pub trait CanInitTrackedResource {
     fn init_tracked_resource<R: TrackableResource + Default>(&mut self) -> &mut Self;
 
     fn insert_tracked_resource<R: TrackableResource>(&mut self, initial_value: R) -> &mut Self;
+
+    /// Add a tracked resource which is persisted through reflection rather than serde
+    fn init_reflect_tracked_resource<R: ReflectTrackableResource + Default>(&mut self)
+        -> &mut Self;
+
+    /// Add a tracked resource which is persisted through reflection rather than serde
+    fn insert_reflect_tracked_resource<R: ReflectTrackableResource>(
+        &mut self,
+        initial_value: R,
+    ) -> &mut Self;
 }
 
 impl CanInitTrackedResource for App {
     fn init_tracked_resource<R: TrackableResource + Default>(&mut self) -> &mut Self {
-        #[cfg(feature = "bevy_pkv")]
         self.add_plugins(crate::tracked_resource::TrackedResourcePlugin::<R>::default());
-        #[cfg(not(feature = "bevy_pkv"))]
-        self.init_resource::<R>();
         self
     }
 
     fn insert_tracked_resource<R: TrackableResource>(&mut self, initial_value: R) -> &mut Self {
-        #[cfg(feature = "bevy_pkv")]
         self.add_plugins(crate::tracked_resource::TrackedResourcePlugin::<R>::new(
             initial_value,
         ));
-        #[cfg(not(feature = "bevy_pkv"))]
-        self.insert_resource::<R>(initial_value);
+        self
+    }
+
+    fn init_reflect_tracked_resource<R: ReflectTrackableResource + Default>(
+        &mut self,
+    ) -> &mut Self {
+        self.insert_reflect_tracked_resource(R::default())
+    }
+
+    fn insert_reflect_tracked_resource<R: ReflectTrackableResource>(
+        &mut self,
+        initial_value: R,
+    ) -> &mut Self {
+        self.add_plugins(
+            crate::tracked_resource::reflect::ReflectTrackedResourcePlugin::new(initial_value),
+        );
+        self
+    }
+}
+
+/// Save components on marked entities and respawn them when the app starts
+pub trait CanTrackEntities {
+    fn track_component<M: TrackedEntityMarker, C: TrackableComponent>(&mut self) -> &mut Self;
+}
+
+impl CanTrackEntities for App {
+    fn track_component<M: TrackedEntityMarker, C: TrackableComponent>(&mut self) -> &mut Self {
+        crate::tracked_resource::entities::track_component::<M, C>(self);
+        self
+    }
+}
+
+/// Sync tracked resources with the server in the [`tracked_resource::sync::TrackedSync`] resource.
+/// Add the tracked resource first.
+pub trait CanSyncTrackedResource {
+    fn sync_tracked_resource<R: TrackableResource>(&mut self) -> &mut Self;
+}
+
+impl CanSyncTrackedResource for App {
+    fn sync_tracked_resource<R: TrackableResource>(&mut self) -> &mut Self {
+        self.add_plugins(crate::tracked_resource::sync::SyncPlugin::<R>::default());
+        self
+    }
+}
+
+/// Record the history of a resource so that changes can be undone with [`tracked_resource::undo::Undo`] events
+pub trait CanMakeUndoable {
+    fn make_undoable<R: bevy::prelude::Resource + Clone>(&mut self, depth: usize) -> &mut Self;
+}
 
+impl CanMakeUndoable for App {
+    fn make_undoable<R: bevy::prelude::Resource + Clone>(&mut self, depth: usize) -> &mut Self {
+        self.add_plugins(crate::tracked_resource::undo::UndoPlugin::<R>::new(depth));
         self
     }
 }
 
 pub trait CanRegisterAsyncEvent {
     fn register_async_event<E: Event>(&mut self) -> &mut Self;
+
+    /// Register an async event with a bounded queue or a limit on how many events are sent each frame
+    fn register_async_event_with_options<E: Event>(
+        &mut self,
+        options: AsyncEventOptions,
+    ) -> &mut Self;
+
+    /// Forward events to [`async_event_reader::AsyncEventReader`]s so that async tasks can receive them.
+    /// Each reader keeps at most `capacity` events.
+    fn register_async_event_reader<E: Event + Clone>(&mut self, capacity: usize) -> &mut Self;
 }
 
 impl CanRegisterAsyncEvent for App {
@@ -57,4 +207,89 @@ impl CanRegisterAsyncEvent for App {
         self.add_plugins(crate::async_event_writer::AsyncEventPlugin::<E>::default());
         self
     }
+
+    fn register_async_event_with_options<E: Event>(
+        &mut self,
+        options: AsyncEventOptions,
+    ) -> &mut Self {
+        self.add_plugins(crate::async_event_writer::AsyncEventPlugin::<E>::new(options));
+        self
+    }
+
+    fn register_async_event_reader<E: Event + Clone>(&mut self, capacity: usize) -> &mut Self {
+        self.add_plugins(crate::async_event_reader::AsyncEventReaderPlugin::<E>::new(
+            capacity,
+        ));
+        self
+    }
+}
+
+/// Let async tasks run code against the world with [`async_world::AsyncWorld`]
+pub trait CanUseAsyncWorld {
+    fn add_async_world(&mut self) -> &mut Self;
+}
+
+impl CanUseAsyncWorld for App {
+    fn add_async_world(&mut self) -> &mut Self {
+        if !self.is_plugin_added::<crate::async_world::AsyncWorldPlugin>() {
+            self.add_plugins(crate::async_world::AsyncWorldPlugin);
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::Resource;
+    use serde::{Deserialize, Serialize};
+
+    use crate::{
+        tracked_resource::{
+            migration::{load_versioned, MigrationError},
+            save_policy::SavePolicy,
+        },
+        TrackableResource,
+    };
+    extern crate self as nice_bevy_utils;
+
+    #[test]
+    pub fn test_derive_trackable_resource() {
+        #[derive(Clone, Default, Resource, Serialize, Deserialize, TrackableResource)]
+        struct Coins(u32);
+
+        #[derive(Debug, Clone, PartialEq, Resource, Serialize, Deserialize, TrackableResource)]
+        #[tracked(key = "settings", version = 1, migrate = migrate, on_loaded = Settings::clamp)]
+        #[tracked(save_policy = SavePolicy::Debounced(bevy::utils::Duration::from_secs(1)))]
+        struct Settings {
+            volume: f32,
+        }
+
+        impl Settings {
+            fn clamp(&mut self) {
+                self.volume = self.volume.clamp(0.0, 1.0);
+            }
+        }
+
+        fn migrate(
+            from_version: u32,
+            value: serde_json::Value,
+        ) -> Result<serde_json::Value, MigrationError> {
+            match from_version {
+                0 => Ok(serde_json::json!({ "volume": value["vol"] })),
+                _ => Err(MigrationError::Missing { from_version }),
+            }
+        }
+
+        assert_eq!(Coins::KEY, "nice_bevy_utils::tests::Coins");
+        assert_eq!(Settings::KEY, "settings");
+        assert_eq!(Settings::VERSION, 1);
+        assert_eq!(
+            Settings::SAVE_POLICY,
+            SavePolicy::Debounced(bevy::utils::Duration::from_secs(1))
+        );
+
+        let mut settings: Settings = load_versioned(serde_json::json!({"vol": 2.0})).unwrap();
+        settings.on_loaded();
+        assert_eq!(settings, Settings { volume: 1.0 });
+    }
 }
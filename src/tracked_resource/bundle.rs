@@ -0,0 +1,285 @@
+use std::{any::Any, collections::BTreeMap, fmt::Display};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::TrackableResource;
+
+use super::{
+    error::PersistenceError,
+    migration::{load_versioned, VersionedRef},
+    reflect::{load_payload, to_data, ReflectTrackableResource},
+    registry::TrackedResourceRegistry,
+};
+
+/// The current version of the bundle format
+pub const BUNDLE_VERSION: u32 = 1;
+
+/// Every tracked resource in an app, in one document.
+/// Use this to move progress between devices or to attach state to a bug report.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrackedResourceBundle {
+    pub version: u32,
+    /// The stored payload of each resource, by key
+    pub resources: BTreeMap<String, Value>,
+}
+
+#[derive(Debug)]
+pub enum BundleError {
+    /// The bundle could not be read or written
+    Json(serde_json::Error),
+    /// The bundle was written by a newer version of this crate
+    UnsupportedVersion(u32),
+    /// A resource could not be serialized
+    Serialize {
+        key: String,
+        error: serde_json::Error,
+    },
+    /// A resource in the bundle could not be loaded or was not valid
+    Load {
+        key: String,
+        error: PersistenceError,
+    },
+}
+
+impl Display for BundleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BundleError::Json(e) => e.fmt(f),
+            BundleError::UnsupportedVersion(version) => write!(
+                f,
+                "Bundle version {version} is newer than the current version {BUNDLE_VERSION}"
+            ),
+            BundleError::Serialize { key, error } => {
+                write!(f, "Could not serialize {key}: {error}")
+            }
+            BundleError::Load { key, error } => write!(f, "Could not load {key}: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for BundleError {}
+
+impl From<serde_json::Error> for BundleError {
+    fn from(value: serde_json::Error) -> Self {
+        Self::Json(value)
+    }
+}
+
+/// A value from a bundle that has been loaded and validated, ready to be applied
+type CheckedValue = Result<Box<dyn Any>, PersistenceError>;
+
+/// Type erased functions for moving a tracked resource in and out of a bundle
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct BundleFns {
+    export: fn(&World) -> Result<Value, serde_json::Error>,
+    check: fn(&World, Value) -> CheckedValue,
+    apply: fn(&mut World, Box<dyn Any>),
+    on_loaded: fn(&mut World),
+}
+
+impl BundleFns {
+    pub fn new<T: TrackableResource>() -> Self {
+        Self {
+            export: |world| {
+                let value = world.resource::<T>().before_save();
+                serde_json::to_value(VersionedRef::new(&*value))
+            },
+            check: |_, payload| {
+                let mut value = load_versioned::<T>(payload)?;
+                if value.validate().is_err() {
+                    value.repair();
+                    value.validate()?;
+                }
+                Ok(Box::new(value))
+            },
+            apply: |world, value| {
+                if let Ok(value) = value.downcast::<T>() {
+                    *world.resource_mut::<T>() = *value;
+                }
+            },
+            on_loaded: |world| world.resource_mut::<T>().on_loaded(),
+        }
+    }
+
+    pub fn new_reflect<R: ReflectTrackableResource>() -> Self {
+        Self {
+            export: |world| {
+                let registry = world.resource::<AppTypeRegistry>().read();
+                let data = to_data(world.resource::<R>(), &registry)?;
+                serde_json::to_value(VersionedRef {
+                    version: R::VERSION,
+                    data: &data,
+                })
+            },
+            check: |world, payload| {
+                let registry = world.resource::<AppTypeRegistry>().read();
+                Ok(Box::new(load_payload::<R>(payload, &registry)?))
+            },
+            apply: |world, value| {
+                if let Ok(value) = value.downcast::<R>() {
+                    *world.resource_mut::<R>() = *value;
+                }
+            },
+            on_loaded: |world| world.resource_mut::<R>().on_loaded(),
+        }
+    }
+}
+
+impl TrackedResourceBundle {
+    /// Collect the current value of every tracked resource
+    pub fn export(world: &World) -> Result<Self, BundleError> {
+        let mut resources = BTreeMap::new();
+        for (key, fns) in world.resource::<TrackedResourceRegistry>().bundle_fns() {
+            let payload = (fns.export)(world).map_err(|error| BundleError::Serialize {
+                key: key.to_string(),
+                error,
+            })?;
+            resources.insert(key.to_string(), payload);
+        }
+
+        Ok(Self {
+            version: BUNDLE_VERSION,
+            resources,
+        })
+    }
+
+    /// Replace tracked resources with the values in this bundle.
+    /// Every value is checked and validated first, so if any of them cannot be loaded no resource is changed.
+    /// Resources that are not in the bundle keep their current values and keys that are not tracked are ignored.
+    ///
+    /// The new values are saved like any other change.
+    pub fn import(self, world: &mut World) -> Result<(), BundleError> {
+        if self.version > BUNDLE_VERSION {
+            return Err(BundleError::UnsupportedVersion(self.version));
+        }
+
+        let registry = world.resource::<TrackedResourceRegistry>();
+        let mut checked = vec![];
+        for (key, payload) in self.resources {
+            let Some(fns) = registry.bundle_fns_for(&key) else {
+                warn!("Bundle contains {key} which is not a tracked resource");
+                continue;
+            };
+            match (fns.check)(world, payload) {
+                Ok(value) => checked.push((fns, value)),
+                Err(error) => return Err(BundleError::Load { key, error }),
+            }
+        }
+
+        let mut applied = vec![];
+        for (fns, value) in checked {
+            (fns.apply)(world, value);
+            applied.push(fns);
+        }
+        for fns in applied {
+            (fns.on_loaded)(world);
+        }
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, BundleError> {
+        Ok(serde_json::to_vec(self)?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, BundleError> {
+        Ok(serde_json::from_slice(bytes)?)
+    }
+
+    pub fn to_json(&self) -> Result<String, BundleError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, BundleError> {
+        Ok(serde_json::from_str(json)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::{
+        tracked_resource::{
+            storage::{MemoryStorage, TrackedStorage},
+            test_utils::{tracked_app, Coins},
+        },
+        CanInitTrackedResource,
+    };
+
+    use super::*;
+
+    #[derive(Debug, Default, Clone, PartialEq, Resource, Serialize, Deserialize)]
+    struct Name {
+        name: String,
+        loads: u32,
+    }
+
+    impl TrackableResource for Name {
+        const KEY: &'static str = "name";
+
+        fn on_loaded(&mut self) {
+            self.loads += 1;
+        }
+    }
+
+    fn app() -> App {
+        tracked_app(TrackedStorage::new(MemoryStorage::default()), |app| {
+            app.init_tracked_resource::<Coins>();
+            app.init_tracked_resource::<Name>();
+        })
+    }
+
+    #[test]
+    pub fn test_export_and_import() {
+        let mut from = app();
+        from.world_mut().resource_mut::<Coins>().0 = 12;
+        from.world_mut().resource_mut::<Name>().name = "Alice".to_string();
+
+        let json = TrackedResourceBundle::export(from.world())
+            .unwrap()
+            .to_json()
+            .unwrap();
+
+        let mut to = app();
+        TrackedResourceBundle::from_json(&json)
+            .unwrap()
+            .import(to.world_mut())
+            .unwrap();
+        to.update();
+
+        assert_eq!(to.world().resource::<Coins>(), &Coins(12));
+        assert_eq!(
+            to.world().resource::<Name>(),
+            &Name {
+                name: "Alice".to_string(),
+                loads: 2
+            }
+        );
+        assert_eq!(
+            to.world()
+                .resource::<TrackedStorage>()
+                .get(Coins::KEY)
+                .unwrap(),
+            Some(br#"{"version":0,"data":12}"#.to_vec())
+        );
+    }
+
+    #[test]
+    pub fn test_invalid_entry_changes_nothing() {
+        let mut app = app();
+        let bundle = TrackedResourceBundle {
+            version: BUNDLE_VERSION,
+            resources: BTreeMap::from([
+                ("coins".to_string(), json!({"version": 0, "data": 12})),
+                ("name".to_string(), json!({"version": 0, "data": 12})),
+            ]),
+        };
+
+        let err = bundle.import(app.world_mut()).unwrap_err();
+
+        assert!(matches!(err, BundleError::Load { key, .. } if key == "name"));
+        assert_eq!(app.world().resource::<Coins>(), &Coins(0));
+    }
+}
@@ -0,0 +1,81 @@
+use std::any::type_name;
+
+use crate::TrackableResource;
+
+use super::{
+    error::{FailurePolicy, PersistenceError, ValidationError},
+    migration::{decode, to_bytes, Decoded, LoadError},
+    save_policy::SavePolicy,
+};
+
+/// How a kind of tracked value is encoded in storage.
+/// Tracked resources, reflected resources and tracked entities are all loaded and saved by [`super::plugin::Persistence`] through this.
+pub(crate) trait TrackedCodec: Send + Sync + 'static {
+    type Value: Send + Sync + 'static;
+    /// Anything encoding and decoding need from the app, like the type registry
+    type Context: Send + Sync + 'static;
+
+    const KEY: &'static str;
+    const SAVE_POLICY: SavePolicy;
+    const SAVE_IN_BACKGROUND: bool;
+    const FAILURE_POLICY: FailurePolicy;
+
+    /// The name used for the value in logs and the registry
+    fn type_name() -> &'static str;
+
+    fn clone_value(value: &Self::Value) -> Self::Value;
+
+    fn encode(value: &Self::Value, context: &Self::Context) -> Result<Vec<u8>, PersistenceError>;
+
+    fn decode(
+        bytes: &[u8],
+        default: &Self::Value,
+        context: &Self::Context,
+    ) -> Result<Decoded<Self::Value>, LoadError>;
+
+    fn validate(_value: &Self::Value) -> Result<(), ValidationError> {
+        Ok(())
+    }
+
+    fn repair(_value: &mut Self::Value) {}
+
+    fn on_loaded(_value: &mut Self::Value) {}
+}
+
+impl<T: TrackableResource> TrackedCodec for T {
+    type Value = T;
+    type Context = ();
+
+    const KEY: &'static str = <T as TrackableResource>::KEY;
+    const SAVE_POLICY: SavePolicy = <T as TrackableResource>::SAVE_POLICY;
+    const SAVE_IN_BACKGROUND: bool = <T as TrackableResource>::SAVE_IN_BACKGROUND;
+    const FAILURE_POLICY: FailurePolicy = <T as TrackableResource>::FAILURE_POLICY;
+
+    fn type_name() -> &'static str {
+        type_name::<T>()
+    }
+
+    fn clone_value(value: &T) -> T {
+        value.clone()
+    }
+
+    fn encode(value: &T, _context: &()) -> Result<Vec<u8>, PersistenceError> {
+        to_bytes(&*value.before_save()).map_err(PersistenceError::Format)
+    }
+
+    fn decode(bytes: &[u8], default: &T, _context: &()) -> Result<Decoded<T>, LoadError> {
+        decode(bytes, default)
+    }
+
+    fn validate(value: &T) -> Result<(), ValidationError> {
+        value.validate()
+    }
+
+    fn repair(value: &mut T) {
+        value.repair();
+    }
+
+    fn on_loaded(value: &mut T) {
+        value.on_loaded();
+    }
+}
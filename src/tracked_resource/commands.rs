@@ -0,0 +1,74 @@
+use std::marker::PhantomData;
+
+use bevy::prelude::*;
+
+/// Set a tracked resource back to its default value. The default value will be saved.
+#[derive(Debug, Event)]
+pub struct ResetTrackedResource<R>(PhantomData<R>);
+
+/// Discard unsaved changes to a tracked resource and load it from storage again
+#[derive(Debug, Event)]
+pub struct ReloadTrackedResource<R>(PhantomData<R>);
+
+/// Remove the stored value of a tracked resource, including any backup, and set it back to its default value.
+/// Nothing is stored until the resource next changes.
+#[derive(Debug, Event)]
+pub struct DeleteTrackedResource<R>(PhantomData<R>);
+
+/// Set every tracked resource back to its default value
+#[derive(Debug, Default, Event)]
+pub struct ResetAllTrackedResources;
+
+macro_rules! impl_default {
+    ($($event:ident),*) => {
+        $(
+            impl<R> Default for $event<R> {
+                fn default() -> Self {
+                    Self(PhantomData)
+                }
+            }
+        )*
+    };
+}
+
+impl_default!(
+    ResetTrackedResource,
+    ReloadTrackedResource,
+    DeleteTrackedResource
+);
+
+pub trait TrackedResourceCommands {
+    fn reset_tracked_resource<R: Resource>(&mut self);
+
+    fn reload_tracked_resource<R: Resource>(&mut self);
+
+    fn delete_tracked_resource<R: Resource>(&mut self);
+
+    fn reset_all_tracked_resources(&mut self);
+}
+
+impl<'w, 's> TrackedResourceCommands for Commands<'w, 's> {
+    fn reset_tracked_resource<R: Resource>(&mut self) {
+        self.add(|world: &mut World| {
+            world.send_event(ResetTrackedResource::<R>::default());
+        });
+    }
+
+    fn reload_tracked_resource<R: Resource>(&mut self) {
+        self.add(|world: &mut World| {
+            world.send_event(ReloadTrackedResource::<R>::default());
+        });
+    }
+
+    fn delete_tracked_resource<R: Resource>(&mut self) {
+        self.add(|world: &mut World| {
+            world.send_event(DeleteTrackedResource::<R>::default());
+        });
+    }
+
+    fn reset_all_tracked_resources(&mut self) {
+        self.add(|world: &mut World| {
+            world.send_event(ResetAllTrackedResources);
+        });
+    }
+}
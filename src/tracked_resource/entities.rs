@@ -0,0 +1,494 @@
+use std::{any::type_name, collections::BTreeMap, marker::PhantomData};
+
+use bevy::{
+    ecs::{event::ManualEventReader, system::SystemState, world::EntityRef},
+    prelude::*,
+    utils::Instant,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+use super::{
+    codec,
+    error::{FailurePolicy, PersistenceError, TrackedOperation, TrackedResourceError},
+    migration::{split_payload, Decoded, LoadError, VersionedRef},
+    mode::PersistenceMode,
+    plugin::{Persistence, TrackedState},
+    registry::TrackedResourceRegistry,
+    save_policy::{PendingSave, SavePolicy, SaveTrackedResources},
+    slots::SaveSlot,
+    storage::ensure_storage,
+};
+
+/// A marker component for entities whose tracked components are saved together under `KEY`.
+/// Every marked entity is respawned with its tracked components when the app starts.
+pub trait TrackedEntityMarker: Component + Default {
+    const KEY: &'static str;
+
+    /// When changes to the marked entities are written to storage
+    const SAVE_POLICY: SavePolicy = SavePolicy::Immediate;
+
+    /// What to do when the marked entities cannot be loaded or saved
+    const FAILURE_POLICY: FailurePolicy = FailurePolicy::Log;
+}
+
+/// A component which is saved on entities with a [`TrackedEntityMarker`]
+pub trait TrackableComponent: Component + Serialize + DeserializeOwned {
+    /// The name of this component within each saved entity
+    const KEY: &'static str;
+}
+
+/// Type erased functions for saving and loading one kind of component
+#[derive(Clone, Copy)]
+struct ComponentFns {
+    key: &'static str,
+    save: fn(&EntityRef) -> Option<Result<Value, serde_json::Error>>,
+    load: fn(&mut EntityWorldMut, Value) -> Result<(), serde_json::Error>,
+}
+
+impl ComponentFns {
+    fn new<C: TrackableComponent>() -> Self {
+        Self {
+            key: C::KEY,
+            save: |entity| entity.get::<C>().map(serde_json::to_value),
+            load: |entity, value| {
+                entity.insert(serde_json::from_value::<C>(value)?);
+                Ok(())
+            },
+        }
+    }
+}
+
+/// The components that are saved on entities marked with `M`
+#[derive(Resource)]
+struct TrackedComponents<M> {
+    components: Vec<ComponentFns>,
+    phantom: PhantomData<M>,
+}
+
+/// Each saved entity is a map from component keys to component values
+type StoredEntities = Vec<BTreeMap<String, Value>>;
+
+/// Stores every entity marked with `M` under [`TrackedEntityMarker::KEY`]
+struct TrackedEntities<M>(PhantomData<M>);
+
+impl<M: TrackedEntityMarker> codec::TrackedCodec for TrackedEntities<M> {
+    type Value = StoredEntities;
+    type Context = ();
+
+    const KEY: &'static str = M::KEY;
+    const SAVE_POLICY: SavePolicy = M::SAVE_POLICY;
+    const SAVE_IN_BACKGROUND: bool = false;
+    const FAILURE_POLICY: FailurePolicy = M::FAILURE_POLICY;
+
+    fn type_name() -> &'static str {
+        type_name::<M>()
+    }
+
+    fn clone_value(value: &StoredEntities) -> StoredEntities {
+        value.clone()
+    }
+
+    fn encode(value: &StoredEntities, _context: &()) -> Result<Vec<u8>, PersistenceError> {
+        serde_json::to_vec(&VersionedRef {
+            version: 0,
+            data: value,
+        })
+        .map_err(PersistenceError::Serialize)
+    }
+
+    fn decode(
+        bytes: &[u8],
+        _default: &StoredEntities,
+        _context: &(),
+    ) -> Result<Decoded<StoredEntities>, LoadError> {
+        let payload = serde_json::from_slice(bytes).map_err(LoadError::Deserialize)?;
+        let (_, data) = split_payload(payload)?;
+        let value = serde_json::from_value(data).map_err(LoadError::Deserialize)?;
+        Ok(Decoded {
+            value,
+            tampered: false,
+            replaced_fields: vec![],
+        })
+    }
+}
+
+type EntityPersistence<M> = SystemState<Persistence<'static, TrackedEntities<M>>>;
+
+pub(crate) struct TrackedEntitiesPlugin<M>(PhantomData<M>);
+
+impl<M> Default for TrackedEntitiesPlugin<M> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<M: TrackedEntityMarker> Plugin for TrackedEntitiesPlugin<M> {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TrackedResourceRegistry>();
+        app.init_resource::<PersistenceMode>();
+        app.world_mut()
+            .resource_mut::<TrackedResourceRegistry>()
+            .register_key(M::KEY, type_name::<M>());
+
+        app.insert_resource(TrackedComponents::<M> {
+            components: vec![],
+            phantom: PhantomData,
+        });
+        app.insert_resource(TrackedState::<TrackedEntities<M>>::new(vec![], ()));
+        app.add_event::<TrackedResourceError>();
+        app.add_systems(
+            PostUpdate,
+            (track_marker_changes::<M>, save_entities::<M>)
+                .chain()
+                .in_set(SaveTrackedResources),
+        );
+        app.add_systems(Last, flush_on_exit::<M>);
+    }
+
+    fn finish(&self, app: &mut App) {
+        let world = app.world_mut();
+        ensure_storage(world);
+        let slot = world.get_resource_or_insert_with(SaveSlot::default).clone();
+        let mut persistence = EntityPersistence::<M>::new(world);
+        load::<M>(world, &mut persistence, &slot);
+    }
+
+    fn name(&self) -> &str {
+        type_name::<Self>()
+    }
+}
+
+/// Start saving a component on entities marked with `M`
+pub(crate) fn track_component<M: TrackedEntityMarker, C: TrackableComponent>(app: &mut App) {
+    if !app.is_plugin_added::<TrackedEntitiesPlugin<M>>() {
+        app.add_plugins(TrackedEntitiesPlugin::<M>::default());
+    }
+
+    let mut tracked = app.world_mut().resource_mut::<TrackedComponents<M>>();
+    if tracked.components.iter().any(|x| x.key == C::KEY) {
+        panic!(
+            "Two components tracked on {} use the key {:?}",
+            type_name::<M>(),
+            C::KEY
+        );
+    }
+    tracked.components.push(ComponentFns::new::<C>());
+
+    app.add_systems(
+        PostUpdate,
+        track_component_changes::<M, C>
+            .before(save_entities::<M>)
+            .in_set(SaveTrackedResources),
+    );
+}
+
+fn track_marker_changes<M: TrackedEntityMarker>(
+    added: Query<(), Added<M>>,
+    mut removed: RemovedComponents<M>,
+    mut state: ResMut<TrackedState<TrackedEntities<M>>>,
+    mode: Res<PersistenceMode>,
+) {
+    let changed = !added.is_empty() || removed.read().count() > 0;
+    if changed && mode.can_write() {
+        state.pending.mark_changed(Instant::now());
+    }
+}
+
+fn track_component_changes<M: TrackedEntityMarker, C: TrackableComponent>(
+    changed: Query<(), (With<M>, Changed<C>)>,
+    marked: Query<(), With<M>>,
+    mut removed: RemovedComponents<C>,
+    mut state: ResMut<TrackedState<TrackedEntities<M>>>,
+    mode: Res<PersistenceMode>,
+) {
+    // Entities that lost their marker as well are seen by `track_marker_changes`
+    let removed = removed.read().filter(|x| marked.contains(*x)).count();
+    let is_changed = !changed.is_empty() || removed > 0;
+    if is_changed && mode.can_write() {
+        state.pending.mark_changed(Instant::now());
+    }
+}
+
+fn save_entities<M: TrackedEntityMarker>(
+    world: &mut World,
+    persistence: &mut EntityPersistence<M>,
+) {
+    let now = Instant::now();
+    let slot = world
+        .get_resource::<SaveSlot>()
+        .cloned()
+        .unwrap_or_default();
+    let needs_load = persistence.get_mut(world).needs_load(&slot);
+    let mut state = world.resource_mut::<TrackedState<TrackedEntities<M>>>();
+
+    if std::mem::take(&mut state.skip_change) {
+        state.pending = PendingSave::default();
+    }
+
+    if needs_load {
+        // Save any changes to the old slot and then replace the entities with the ones in the new slot
+        if state.pending.is_dirty() {
+            save::<M>(world, persistence, now);
+        }
+        let entities: Vec<Entity> = world
+            .query_filtered::<Entity, With<M>>()
+            .iter(world)
+            .collect();
+        for entity in entities {
+            world.entity_mut(entity).despawn_recursive();
+        }
+        load::<M>(world, persistence, &slot);
+        return;
+    }
+
+    if state.pending.should_save(M::SAVE_POLICY, now) {
+        save::<M>(world, persistence, now);
+    }
+}
+
+/// Save any changes that are still waiting on the save policy before the app closes
+fn flush_on_exit<M: TrackedEntityMarker>(
+    world: &mut World,
+    mut exit: Local<ManualEventReader<AppExit>>,
+    persistence: &mut EntityPersistence<M>,
+) {
+    let events = world.resource::<Events<AppExit>>();
+    if exit.read(events).count() > 0
+        && world
+            .resource::<TrackedState<TrackedEntities<M>>>()
+            .pending
+            .is_dirty()
+    {
+        save::<M>(world, persistence, Instant::now());
+    }
+}
+
+/// Write every marked entity to the current slot
+fn save<M: TrackedEntityMarker>(
+    world: &mut World,
+    persistence: &mut EntityPersistence<M>,
+    now: Instant,
+) {
+    let components = world.resource::<TrackedComponents<M>>().components.clone();
+
+    let mut query = world.query_filtered::<(Entity, EntityRef), With<M>>();
+    let mut entities: Vec<_> = query.iter(world).collect();
+    entities.sort_by_key(|(entity, _)| *entity);
+
+    let mut stored: StoredEntities = vec![];
+    let mut result = Ok(());
+    for (_, entity) in entities {
+        let mut values = BTreeMap::new();
+        for fns in components.iter() {
+            match (fns.save)(&entity) {
+                Some(Ok(value)) => {
+                    values.insert(fns.key.to_string(), value);
+                }
+                Some(Err(error)) => result = Err(PersistenceError::Serialize(error)),
+                None => {}
+            }
+        }
+        stored.push(values);
+    }
+
+    persistence.get_mut(world).save_with(now, false, |context| {
+        result?;
+        <TrackedEntities<M> as codec::TrackedCodec>::encode(&stored, context)
+    });
+}
+
+/// Spawn the entities stored in a slot
+fn load<M: TrackedEntityMarker>(
+    world: &mut World,
+    persistence: &mut EntityPersistence<M>,
+    slot: &SaveSlot,
+) {
+    let key = slot.key(M::KEY);
+    let mut loading = persistence.get_mut(world);
+    let stored = loading.load(slot);
+    loading.state.slot = slot.clone();
+    loading.state.pending = PendingSave::default();
+    loading.state.skip_change = true;
+
+    let errors = spawn::<M>(world, &key, stored);
+    let mut loading = persistence.get_mut(world);
+    for error in errors {
+        let error = PersistenceError::Load(LoadError::Deserialize(error));
+        loading.report_error(key.clone(), TrackedOperation::Load, error);
+    }
+}
+
+/// Returns the errors from components that could not be loaded
+fn spawn<M: TrackedEntityMarker>(
+    world: &mut World,
+    key: &str,
+    stored: StoredEntities,
+) -> Vec<serde_json::Error> {
+    let components = world.resource::<TrackedComponents<M>>().components.clone();
+
+    let mut errors = vec![];
+    for values in stored {
+        let mut entity = world.spawn(M::default());
+        for (component, value) in values {
+            match components.iter().find(|x| x.key == component) {
+                Some(fns) => {
+                    if let Err(error) = (fns.load)(&mut entity, value) {
+                        errors.push(error);
+                    }
+                }
+                None => warn!("Entity saved under {key} has an unknown component {component}"),
+            }
+        }
+    }
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use crate::{
+        tracked_resource::{
+            recovery::{backup_key, quarantine_key},
+            registry::LoadOutcome,
+            storage::{MemoryStorage, TrackedStorage},
+            test_utils::tracked_app,
+        },
+        CanTrackEntities,
+    };
+
+    use super::*;
+
+    #[derive(Debug, Default, Component)]
+    struct Building;
+
+    impl TrackedEntityMarker for Building {
+        const KEY: &'static str = "buildings";
+    }
+
+    #[derive(Debug, Clone, PartialEq, Component, Serialize, Deserialize)]
+    struct Position(i32, i32);
+
+    impl TrackableComponent for Position {
+        const KEY: &'static str = "position";
+    }
+
+    #[derive(Debug, Clone, PartialEq, Component, Serialize, Deserialize)]
+    struct Level(u32);
+
+    impl TrackableComponent for Level {
+        const KEY: &'static str = "level";
+    }
+
+    fn app(storage: TrackedStorage) -> App {
+        tracked_app(storage, |app| {
+            app.track_component::<Building, Position>();
+            app.track_component::<Building, Level>();
+        })
+    }
+
+    fn buildings(app: &mut App) -> Vec<(Position, Option<Level>)> {
+        let world = app.world_mut();
+        let mut query = world.query_filtered::<(&Position, Option<&Level>), With<Building>>();
+        let mut buildings: Vec<_> = query
+            .iter(world)
+            .map(|(position, level)| (position.clone(), level.cloned()))
+            .collect();
+        buildings.sort_by_key(|(position, _)| (position.0, position.1));
+        buildings
+    }
+
+    #[test]
+    pub fn test_entities_are_saved_and_respawned() {
+        let storage = TrackedStorage::new(MemoryStorage::default());
+
+        let mut first = app(storage.clone());
+        first.update();
+        first
+            .world_mut()
+            .spawn((Building, Position(1, 2), Level(3)));
+        first.world_mut().spawn((Building, Position(4, 5)));
+        first.world_mut().spawn(Position(6, 7));
+        first.update();
+
+        let mut second = app(storage.clone());
+        assert_eq!(
+            buildings(&mut second),
+            vec![(Position(1, 2), Some(Level(3))), (Position(4, 5), None)]
+        );
+
+        let entity = second
+            .world_mut()
+            .query_filtered::<Entity, With<Level>>()
+            .single(second.world());
+        second.update();
+        second.world_mut().entity_mut(entity).insert(Level(10));
+        second.update();
+
+        let mut third = app(storage);
+        assert_eq!(
+            buildings(&mut third),
+            vec![(Position(1, 2), Some(Level(10))), (Position(4, 5), None)]
+        );
+    }
+
+    #[test]
+    pub fn test_unmarked_entities_are_ignored() {
+        let mut app = app(TrackedStorage::new(MemoryStorage::default()));
+        app.update();
+        let entity = app.world_mut().spawn(Position(6, 7)).id();
+        app.world_mut().spawn((Building, Level(1)));
+        app.update();
+        let last_saved = |app: &App| {
+            let registry = app.world().resource::<TrackedResourceRegistry>();
+            registry.get(Building::KEY).unwrap().last_saved
+        };
+        let saved = last_saved(&app);
+        assert!(saved.is_some());
+
+        app.world_mut().entity_mut(entity).remove::<Position>();
+        app.update();
+        assert_eq!(last_saved(&app), saved);
+    }
+
+    #[test]
+    pub fn test_unreadable_entities_are_quarantined_and_backup_restored() {
+        let storage = TrackedStorage::new(MemoryStorage::default());
+        let mut first = app(storage.clone());
+        first.update();
+        first.world_mut().spawn((Building, Position(1, 2)));
+        first.update();
+
+        // Loading the saved entities keeps them as the backup
+        app(storage.clone());
+        assert!(storage.get(&backup_key(Building::KEY)).unwrap().is_some());
+
+        let mut storage = storage;
+        storage.set(Building::KEY, b"garbage").unwrap();
+        let mut third = app(storage.clone());
+        assert_eq!(buildings(&mut third), vec![(Position(1, 2), None)]);
+        assert_eq!(
+            third
+                .world()
+                .resource::<TrackedResourceRegistry>()
+                .get(Building::KEY)
+                .unwrap()
+                .last_load,
+            Some(LoadOutcome::RestoredBackup)
+        );
+
+        third.update();
+        third.world_mut().spawn((Building, Position(3, 4)));
+        third.update();
+        assert_eq!(
+            storage.get(&quarantine_key(Building::KEY)).unwrap(),
+            Some(b"garbage".to_vec())
+        );
+        let mut fourth = app(storage);
+        assert_eq!(
+            buildings(&mut fourth),
+            vec![(Position(1, 2), None), (Position(3, 4), None)]
+        );
+    }
+}
@@ -0,0 +1,143 @@
+use std::fmt::Display;
+
+use bevy::{prelude::*, utils::Duration};
+
+use super::{format::FormatError, migration::LoadError, storage::StorageError, sync::SyncError};
+
+/// Sent whenever a tracked resource could not be loaded or saved
+#[derive(Debug, Event)]
+pub struct TrackedResourceError {
+    pub key: String,
+    pub operation: TrackedOperation,
+    pub error: PersistenceError,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TrackedOperation {
+    Load,
+    Save,
+    Delete,
+    Sync,
+}
+
+impl Display for TrackedOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TrackedOperation::Load => write!(f, "load"),
+            TrackedOperation::Save => write!(f, "save"),
+            TrackedOperation::Delete => write!(f, "delete"),
+            TrackedOperation::Sync => write!(f, "sync"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum PersistenceError {
+    Storage(StorageError),
+    Serialize(serde_json::Error),
+    Format(FormatError),
+    Load(LoadError),
+    Invalid(ValidationError),
+    Sync(SyncError),
+}
+
+impl Display for PersistenceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PersistenceError::Storage(e) => e.fmt(f),
+            PersistenceError::Serialize(e) => e.fmt(f),
+            PersistenceError::Format(e) => e.fmt(f),
+            PersistenceError::Load(e) => e.fmt(f),
+            PersistenceError::Invalid(e) => e.fmt(f),
+            PersistenceError::Sync(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for PersistenceError {}
+
+impl From<StorageError> for PersistenceError {
+    fn from(value: StorageError) -> Self {
+        Self::Storage(value)
+    }
+}
+
+impl From<LoadError> for PersistenceError {
+    fn from(value: LoadError) -> Self {
+        Self::Load(value)
+    }
+}
+
+impl From<ValidationError> for PersistenceError {
+    fn from(value: ValidationError) -> Self {
+        Self::Invalid(value)
+    }
+}
+
+/// Returned by [`crate::TrackableResource::validate`] when a value is not valid
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError(pub String);
+
+impl ValidationError {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self(message.into())
+    }
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid value: {}", self.0)
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+/// What to do when a tracked resource cannot be loaded or saved.
+/// A [`TrackedResourceError`] is sent whatever the policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FailurePolicy {
+    /// Panic. Values that are loaded after being repaired or flagged as tampered only send an event.
+    Panic,
+    /// Log the error and carry on. A failed save is not attempted again until the resource next changes.
+    #[default]
+    Log,
+    /// Log the error and try saving again after `backoff`, doubling the wait after each failure.
+    /// Loads are not retried.
+    Retry {
+        max_attempts: u32,
+        backoff: Duration,
+    },
+}
+
+impl FailurePolicy {
+    /// How long to wait before the next attempt, or `None` to give up
+    pub(crate) fn retry_delay(&self, failed_attempts: u32) -> Option<Duration> {
+        match self {
+            FailurePolicy::Retry {
+                max_attempts,
+                backoff,
+            } if failed_attempts < *max_attempts => {
+                Some(backoff.saturating_mul(1 << failed_attempts.saturating_sub(1).min(16)))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_retry_delay() {
+        let policy = FailurePolicy::Retry {
+            max_attempts: 3,
+            backoff: Duration::from_secs(1),
+        };
+
+        assert_eq!(policy.retry_delay(1), Some(Duration::from_secs(1)));
+        assert_eq!(policy.retry_delay(2), Some(Duration::from_secs(2)));
+        assert_eq!(policy.retry_delay(3), None);
+        assert_eq!(FailurePolicy::Log.retry_delay(1), None);
+    }
+}
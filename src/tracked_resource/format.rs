@@ -0,0 +1,272 @@
+use std::fmt::Display;
+#[cfg(feature = "deflate")]
+use std::io::{Read, Write};
+
+#[cfg(feature = "deflate")]
+use flate2::{read::DeflateDecoder, write::DeflateEncoder};
+use serde::Serialize;
+use serde_json::Value;
+
+const RON_TAG: &[u8] = b"// nbu-ron";
+const MESSAGE_PACK_TAG: &[u8] = b"nbu-msgpack:";
+const DEFLATE_TAG: &[u8] = b"nbu-deflate:";
+
+/// Compressed values that inflate to more than this many bytes are rejected, so a corrupt value can't use up all the memory
+#[cfg(feature = "deflate")]
+const MAX_INFLATED_LEN: u64 = 256 * 1024 * 1024;
+
+/// How a tracked resource is encoded in storage.
+///
+/// Stored values are tagged with their format, so values in any format can be read as long as its feature is enabled.
+/// Changing the format of a resource converts its stored value the next time it is saved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    #[default]
+    Json,
+    /// Readable and easy for players to edit. Needs the `ron` feature.
+    #[cfg(feature = "ron")]
+    Ron,
+    /// A compact binary encoding, useful for large resources. Needs the `message_pack` feature.
+    #[cfg(feature = "message_pack")]
+    MessagePack,
+}
+
+/// Whether stored values are compressed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Compression {
+    #[default]
+    None,
+    /// Needs the `deflate` feature
+    #[cfg(feature = "deflate")]
+    Deflate,
+}
+
+#[derive(Debug)]
+pub enum FormatError {
+    Json(serde_json::Error),
+    Ron(String),
+    MessagePack(String),
+    Compression(std::io::Error),
+    /// The stored value inflates to more than this many bytes
+    TooLarge(u64),
+    /// The stored value needs a feature of this crate which is not enabled
+    MissingFeature(&'static str),
+}
+
+impl Display for FormatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FormatError::Json(e) => e.fmt(f),
+            FormatError::Ron(message) => write!(f, "RON: {message}"),
+            FormatError::MessagePack(message) => write!(f, "MessagePack: {message}"),
+            FormatError::Compression(e) => write!(f, "Compression: {e}"),
+            FormatError::TooLarge(limit) => {
+                write!(f, "The stored value inflates to more than {limit} bytes")
+            }
+            FormatError::MissingFeature(feature) => {
+                write!(f, "Reading this value needs the `{feature}` feature")
+            }
+        }
+    }
+}
+
+impl std::error::Error for FormatError {}
+
+impl From<serde_json::Error> for FormatError {
+    fn from(value: serde_json::Error) -> Self {
+        Self::Json(value)
+    }
+}
+
+impl Format {
+    /// Encode a value, tagged with this format unless it is JSON
+    pub(crate) fn encode<S: Serialize>(&self, value: &S) -> Result<Vec<u8>, FormatError> {
+        match self {
+            Format::Json => Ok(serde_json::to_vec(value)?),
+            // RON and MessagePack go through a value tree so that they can always be read back into one for migration
+            #[cfg(feature = "ron")]
+            Format::Ron => {
+                let value = serde_json::to_value(value)?;
+                let ron = ron::ser::to_string_pretty(&value, Default::default())
+                    .map_err(|e| FormatError::Ron(e.to_string()))?;
+
+                let mut bytes = RON_TAG.to_vec();
+                bytes.push(b'\n');
+                bytes.extend_from_slice(ron.as_bytes());
+                Ok(bytes)
+            }
+            #[cfg(feature = "message_pack")]
+            Format::MessagePack => {
+                let value = serde_json::to_value(value)?;
+                let mut bytes = MESSAGE_PACK_TAG.to_vec();
+                rmp_serde::encode::write(&mut bytes, &value)
+                    .map_err(|e| FormatError::MessagePack(e.to_string()))?;
+                Ok(bytes)
+            }
+        }
+    }
+}
+
+impl Compression {
+    pub(crate) fn compress(&self, bytes: Vec<u8>) -> Result<Vec<u8>, FormatError> {
+        match self {
+            Compression::None => Ok(bytes),
+            #[cfg(feature = "deflate")]
+            Compression::Deflate => {
+                let mut encoder =
+                    DeflateEncoder::new(DEFLATE_TAG.to_vec(), flate2::Compression::default());
+                encoder
+                    .write_all(&bytes)
+                    .map_err(FormatError::Compression)?;
+                encoder.finish().map_err(FormatError::Compression)
+            }
+        }
+    }
+}
+
+/// Decode a stored value in any format, compressed or not
+pub(crate) fn decode(bytes: &[u8]) -> Result<Value, FormatError> {
+    match bytes.strip_prefix(DEFLATE_TAG) {
+        #[cfg(feature = "deflate")]
+        Some(compressed) => decode_uncompressed(&inflate(compressed, MAX_INFLATED_LEN)?),
+        #[cfg(not(feature = "deflate"))]
+        Some(_) => Err(FormatError::MissingFeature("deflate")),
+        None => decode_uncompressed(bytes),
+    }
+}
+
+/// Inflate `compressed`, failing if it is larger than `limit`
+#[cfg(feature = "deflate")]
+fn inflate(compressed: &[u8], limit: u64) -> Result<Vec<u8>, FormatError> {
+    let mut inflated = vec![];
+    DeflateDecoder::new(compressed)
+        .take(limit + 1)
+        .read_to_end(&mut inflated)
+        .map_err(FormatError::Compression)?;
+    if inflated.len() as u64 > limit {
+        return Err(FormatError::TooLarge(limit));
+    }
+    Ok(inflated)
+}
+
+fn decode_uncompressed(bytes: &[u8]) -> Result<Value, FormatError> {
+    if bytes.starts_with(RON_TAG) {
+        // The tag is a comment so the whole value is valid RON
+        #[cfg(feature = "ron")]
+        return ron::de::from_bytes(bytes).map_err(|e| FormatError::Ron(e.to_string()));
+        #[cfg(not(feature = "ron"))]
+        return Err(FormatError::MissingFeature("ron"));
+    }
+
+    match bytes.strip_prefix(MESSAGE_PACK_TAG) {
+        #[cfg(feature = "message_pack")]
+        Some(message_pack) => {
+            rmp_serde::from_slice(message_pack).map_err(|e| FormatError::MessagePack(e.to_string()))
+        }
+        #[cfg(not(feature = "message_pack"))]
+        Some(_) => Err(FormatError::MissingFeature("message_pack")),
+        None => Ok(serde_json::from_slice(bytes)?),
+    }
+}
+
+#[cfg(all(test, feature = "ron", feature = "message_pack", feature = "deflate"))]
+mod tests {
+    use bevy::prelude::Resource;
+    use serde::Deserialize;
+
+    use crate::{
+        tracked_resource::migration::{from_bytes, to_bytes},
+        TrackableResource,
+    };
+
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq, Resource, Serialize, Deserialize)]
+    struct Progress {
+        levels: Vec<Option<u32>>,
+        name: String,
+        volume: f32,
+    }
+
+    impl TrackableResource for Progress {
+        const KEY: &'static str = "progress";
+    }
+
+    #[derive(Debug, Clone, PartialEq, Resource, Serialize, Deserialize)]
+    struct RonProgress(Progress);
+
+    impl TrackableResource for RonProgress {
+        const KEY: &'static str = "progress";
+        const FORMAT: Format = Format::Ron;
+    }
+
+    #[derive(Debug, Clone, PartialEq, Resource, Serialize, Deserialize)]
+    struct CompressedProgress(Progress);
+
+    impl TrackableResource for CompressedProgress {
+        const KEY: &'static str = "progress";
+        const FORMAT: Format = Format::MessagePack;
+        const COMPRESSION: Compression = Compression::Deflate;
+    }
+
+    fn progress() -> Progress {
+        Progress {
+            levels: vec![Some(1), None, Some(3)],
+            name: "Alice".to_string(),
+            volume: 0.1,
+        }
+    }
+
+    #[test]
+    pub fn test_formats_round_trip() {
+        let ron = to_bytes(&RonProgress(progress())).unwrap();
+        assert!(String::from_utf8(ron.clone())
+            .unwrap()
+            .contains("\"Alice\""));
+        assert_eq!(from_bytes::<RonProgress>(&ron).unwrap().0, progress());
+
+        let compressed = to_bytes(&CompressedProgress(progress())).unwrap();
+        assert!(compressed.starts_with(DEFLATE_TAG));
+        assert_eq!(
+            from_bytes::<CompressedProgress>(&compressed).unwrap().0,
+            progress()
+        );
+    }
+
+    #[test]
+    pub fn test_any_format_can_be_read() {
+        let json = to_bytes(&progress()).unwrap();
+        let ron = to_bytes(&RonProgress(progress())).unwrap();
+        let compressed = to_bytes(&CompressedProgress(progress())).unwrap();
+
+        assert_eq!(from_bytes::<Progress>(&ron).unwrap(), progress());
+        assert_eq!(from_bytes::<Progress>(&compressed).unwrap(), progress());
+        assert_eq!(
+            from_bytes::<CompressedProgress>(&json).unwrap().0,
+            progress()
+        );
+    }
+
+    #[test]
+    pub fn test_inflated_size_is_limited() {
+        let compressed = Compression::Deflate.compress(vec![b' '; 1000]).unwrap();
+        let compressed = compressed.strip_prefix(DEFLATE_TAG).unwrap();
+
+        assert_eq!(inflate(compressed, 1000).unwrap().len(), 1000);
+        assert!(matches!(
+            inflate(compressed, 999),
+            Err(FormatError::TooLarge(999))
+        ));
+    }
+}
+
+#[cfg(all(test, not(feature = "ron")))]
+mod missing_feature_tests {
+    use super::*;
+
+    #[test]
+    pub fn test_missing_feature_is_reported() {
+        let error = decode(b"// nbu-ron\n(coins: 1)").unwrap_err();
+        assert!(matches!(error, FormatError::MissingFeature("ron")));
+    }
+}
@@ -0,0 +1,419 @@
+use std::fmt::Display;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::TrackableResource;
+
+use super::format::{self, FormatError};
+
+/// A value as it is written to storage, tagged with the schema version of `T`
+#[derive(Debug, Serialize)]
+pub(crate) struct VersionedRef<'a, T> {
+    pub version: u32,
+    pub data: &'a T,
+}
+
+impl<'a, T: TrackableResource> VersionedRef<'a, T> {
+    pub fn new(data: &'a T) -> Self {
+        Self {
+            version: T::VERSION,
+            data,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MigrationError {
+    /// There is no migration from this version
+    Missing { from_version: u32 },
+    /// The stored value was written by a newer version of the resource
+    NewerVersion { stored: u32, current: u32 },
+    /// The migration function could not upgrade the value
+    Failed { from_version: u32, message: String },
+    /// The stored version does not fit in a `u32`
+    InvalidVersion { stored: u64 },
+}
+
+impl Display for MigrationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MigrationError::Missing { from_version } => {
+                write!(f, "No migration from version {from_version}")
+            }
+            MigrationError::NewerVersion { stored, current } => write!(
+                f,
+                "Stored version {stored} is newer than the current version {current}"
+            ),
+            MigrationError::Failed {
+                from_version,
+                message,
+            } => write!(f, "Migration from version {from_version} failed: {message}"),
+            MigrationError::InvalidVersion { stored } => {
+                write!(f, "Stored version {stored} is not a valid version")
+            }
+        }
+    }
+}
+
+impl std::error::Error for MigrationError {}
+
+#[derive(Debug)]
+pub enum LoadError {
+    Migration(MigrationError),
+    /// The stored value could not be decoded
+    Format(FormatError),
+    Deserialize(serde_json::Error),
+    /// The stored value failed its integrity check
+    Tampered,
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoadError::Migration(e) => e.fmt(f),
+            LoadError::Format(e) => e.fmt(f),
+            LoadError::Deserialize(e) => e.fmt(f),
+            LoadError::Tampered => write!(f, "The stored value failed its integrity check"),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+impl From<MigrationError> for LoadError {
+    fn from(value: MigrationError) -> Self {
+        Self::Migration(value)
+    }
+}
+
+/// Split a stored payload into its version and data.
+/// Payloads written before versioning was added are treated as version 0.
+///
+/// A payload is only read as versioned if its only keys are `data` and an unsigned integer `version`.
+/// An unversioned value of a struct with exactly those two fields can't be told apart from a versioned one,
+/// so it is read as versioned too.
+pub(crate) fn split_payload(payload: Value) -> Result<(u32, Value), MigrationError> {
+    match payload {
+        Value::Object(mut map) if map.len() == 2 && map.contains_key("data") => {
+            match map.get("version").and_then(Value::as_u64) {
+                Some(version) => {
+                    let version = u32::try_from(version)
+                        .map_err(|_| MigrationError::InvalidVersion { stored: version })?;
+                    Ok((version, map.remove("data").unwrap_or_default()))
+                }
+                None => Ok((0, Value::Object(map))),
+            }
+        }
+        payload => Ok((0, payload)),
+    }
+}
+
+/// Read a stored payload, migrating it to [`TrackableResource::VERSION`] first if necessary
+pub fn load_versioned<T: TrackableResource>(payload: Value) -> Result<T, LoadError> {
+    let data = migrate_payload::<T>(payload)?;
+    serde_json::from_value(data).map_err(LoadError::Deserialize)
+}
+
+/// Like [`load_versioned`], but fields that are missing or cannot be read are taken from `default`.
+/// Returns the paths of the fields that were replaced.
+///
+/// Only fields of structs and maps can be replaced, so values of other shapes are loaded strictly.
+pub fn load_lenient<T: TrackableResource>(
+    payload: Value,
+    default: &T,
+) -> Result<(T, Vec<String>), LoadError> {
+    let data = migrate_payload::<T>(payload)?;
+    let error = match serde_json::from_value(data.clone()) {
+        Ok(value) => return Ok((value, vec![])),
+        Err(error) => error,
+    };
+
+    let mut merged = serde_json::to_value(default).map_err(LoadError::Deserialize)?;
+    if !merged.is_object() {
+        return Err(LoadError::Deserialize(error));
+    }
+
+    let mut replaced = vec![];
+    fill_fields::<T>(&mut merged, "", data, &mut replaced);
+    let value = serde_json::from_value(merged).map_err(LoadError::Deserialize)?;
+    Ok((value, replaced))
+}
+
+/// Copy each field of `stored` into the object at `pointer` in `merged`, keeping it only if `T` can still be deserialized
+fn fill_fields<T: TrackableResource>(
+    merged: &mut Value,
+    pointer: &str,
+    mut stored: Value,
+    replaced: &mut Vec<String>,
+) {
+    let Some(fields) = merged.pointer(pointer).and_then(Value::as_object) else {
+        return;
+    };
+    let names: Vec<String> = fields.keys().cloned().collect();
+
+    for name in names {
+        let field_pointer = format!("{pointer}/{}", name.replace('~', "~0").replace('/', "~1"));
+        let path = field_pointer[1..].replace('/', ".");
+        let Some(stored_field) = stored.get_mut(&name).map(Value::take) else {
+            replaced.push(path);
+            continue;
+        };
+
+        let Some(slot) = merged.pointer_mut(&field_pointer) else {
+            continue;
+        };
+        let default_field = std::mem::replace(slot, stored_field.clone());
+        if serde_json::from_value::<T>(merged.clone()).is_ok() {
+            continue;
+        }
+
+        // Put the default back and, if both are objects, try their fields one at a time instead
+        let nested = default_field.is_object() && stored_field.is_object();
+        if let Some(slot) = merged.pointer_mut(&field_pointer) {
+            *slot = default_field;
+        }
+        if nested {
+            fill_fields::<T>(merged, &field_pointer, stored_field, replaced);
+        } else {
+            replaced.push(path);
+        }
+    }
+}
+
+/// Split a stored payload and migrate its data to [`TrackableResource::VERSION`]
+fn migrate_payload<T: TrackableResource>(payload: Value) -> Result<Value, MigrationError> {
+    migrate_data(payload, T::VERSION, T::migrate)
+}
+
+/// Split a stored payload and migrate its data to `current_version`
+pub(crate) fn migrate_data(
+    payload: Value,
+    current_version: u32,
+    migrate: fn(u32, Value) -> Result<Value, MigrationError>,
+) -> Result<Value, MigrationError> {
+    let (mut version, mut data) = split_payload(payload)?;
+
+    if version > current_version {
+        return Err(MigrationError::NewerVersion {
+            stored: version,
+            current: current_version,
+        });
+    }
+
+    while version < current_version {
+        data = migrate(version, data)?;
+        version += 1;
+    }
+
+    Ok(data)
+}
+
+/// Serialize a value, tagged with its version, encoded and protected, ready to be stored
+pub fn to_bytes<T: TrackableResource>(value: &T) -> Result<Vec<u8>, FormatError> {
+    let payload = T::FORMAT.encode(&VersionedRef::new(value))?;
+    let payload = T::COMPRESSION.compress(payload)?;
+    Ok(T::PROTECTION.seal(payload))
+}
+
+/// Deserialize a stored value, checking its protection and migrating it if necessary
+pub fn from_bytes<T: TrackableResource>(bytes: &[u8]) -> Result<T, LoadError> {
+    let opened = T::PROTECTION.open(bytes)?;
+    let payload = format::decode(&opened.payload).map_err(LoadError::Format)?;
+    load_versioned(payload)
+}
+
+/// A stored value that has been read by [`decode`]
+pub(crate) struct Decoded<T> {
+    pub value: T,
+    /// The value failed its integrity check but is allowed by [`super::protection::TamperPolicy::Flag`]
+    pub tampered: bool,
+    /// The fields that were taken from the default value because [`TrackableResource::LENIENT`] is set
+    pub replaced_fields: Vec<String>,
+}
+
+/// Like [`from_bytes`], but loads leniently if the resource allows it
+pub(crate) fn decode<T: TrackableResource>(
+    bytes: &[u8],
+    default: &T,
+) -> Result<Decoded<T>, LoadError> {
+    let opened = T::PROTECTION.open(bytes)?;
+    let payload = format::decode(&opened.payload).map_err(LoadError::Format)?;
+    let (value, replaced_fields) = if T::LENIENT {
+        load_lenient(payload, default)?
+    } else {
+        (load_versioned(payload)?, vec![])
+    };
+
+    Ok(Decoded {
+        value,
+        tampered: opened.tampered,
+        replaced_fields,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::Resource;
+    use serde::{Deserialize, Serialize};
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq, Resource, Serialize, Deserialize)]
+    struct Settings {
+        music_volume: f32,
+        sound_volume: f32,
+    }
+
+    impl TrackableResource for Settings {
+        const KEY: &'static str = "settings";
+        const VERSION: u32 = 3;
+
+        fn migrate(from_version: u32, mut value: Value) -> Result<Value, MigrationError> {
+            match from_version {
+                // v1 -> v2: `volume` was renamed to `music_volume`
+                1 => {
+                    let volume = value["volume"].take();
+                    Ok(json!({"music_volume": volume}))
+                }
+                // v2 -> v3: `sound_volume` was added
+                2 => {
+                    value["sound_volume"] = json!(0.5);
+                    Ok(value)
+                }
+                _ => Err(MigrationError::Missing { from_version }),
+            }
+        }
+    }
+
+    #[test]
+    pub fn test_v1_payload_loads_as_v3() {
+        let payload = json!({"version": 1, "data": {"volume": 0.25}});
+
+        let settings = load_versioned::<Settings>(payload).unwrap();
+
+        assert_eq!(
+            settings,
+            Settings {
+                music_volume: 0.25,
+                sound_volume: 0.5
+            }
+        );
+    }
+
+    #[test]
+    pub fn test_current_payload_round_trips() {
+        let settings = Settings {
+            music_volume: 0.1,
+            sound_volume: 0.2,
+        };
+        let payload = serde_json::to_value(VersionedRef::new(&settings)).unwrap();
+
+        assert_eq!(load_versioned::<Settings>(payload).unwrap(), settings);
+    }
+
+    #[test]
+    pub fn test_stored_bytes_round_trip() {
+        let settings = Settings {
+            music_volume: 0.1,
+            sound_volume: 0.2,
+        };
+        let bytes = to_bytes(&settings).unwrap();
+
+        assert_eq!(
+            bytes,
+            serde_json::to_vec(&VersionedRef::new(&settings)).unwrap()
+        );
+        assert_eq!(from_bytes::<Settings>(&bytes).unwrap(), settings);
+    }
+
+    #[test]
+    pub fn test_unversioned_payload_is_version_zero() {
+        let payload = json!({"volume": 0.25});
+
+        let err = load_versioned::<Settings>(payload).unwrap_err();
+
+        assert!(matches!(
+            err,
+            LoadError::Migration(MigrationError::Missing { from_version: 0 })
+        ));
+    }
+
+    #[test]
+    pub fn test_newer_payload_is_rejected() {
+        let payload = json!({"version": 4, "data": {}});
+
+        let err = load_versioned::<Settings>(payload).unwrap_err();
+
+        assert!(matches!(
+            err,
+            LoadError::Migration(MigrationError::NewerVersion {
+                stored: 4,
+                current: 3
+            })
+        ));
+    }
+
+    #[test]
+    pub fn test_version_too_large_is_rejected() {
+        let payload = json!({"version": u64::from(u32::MAX) + 1, "data": {}});
+
+        let err = load_versioned::<Settings>(payload).unwrap_err();
+
+        assert!(matches!(
+            err,
+            LoadError::Migration(MigrationError::InvalidVersion { stored: 4294967296 })
+        ));
+    }
+
+    #[derive(Debug, Clone, PartialEq, Default, Resource, Serialize, Deserialize)]
+    struct Profile {
+        name: String,
+        level: u32,
+        difficulty: u8,
+        audio: Audio,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+    struct Audio {
+        music: f32,
+        sfx: f32,
+    }
+
+    impl TrackableResource for Profile {
+        const KEY: &'static str = "profile";
+        const LENIENT: bool = true;
+    }
+
+    #[test]
+    pub fn test_lenient_load_fills_fields_from_default() {
+        let payload = json!({
+            "name": "Alice",
+            "level": -1,
+            "audio": {"music": "loud", "sfx": 0.5},
+            "removed": true
+        });
+        let default = Profile {
+            level: 1,
+            difficulty: 2,
+            ..Default::default()
+        };
+
+        let (profile, replaced) = load_lenient(payload, &default).unwrap();
+
+        assert_eq!(
+            profile,
+            Profile {
+                name: "Alice".to_string(),
+                level: 1,
+                difficulty: 2,
+                audio: Audio {
+                    music: 0.0,
+                    sfx: 0.5
+                }
+            }
+        );
+        assert_eq!(replaced, vec!["audio.music", "difficulty", "level"]);
+    }
+}
@@ -0,0 +1,82 @@
+pub mod bundle;
+mod codec;
+pub mod commands;
+pub mod entities;
+pub mod error;
+pub mod format;
+pub mod migration;
+pub mod mode;
+mod plugin;
+pub mod protection;
+pub mod recovery;
+pub mod reflect;
+pub mod registry;
+pub mod save_policy;
+#[cfg(feature = "share_code")]
+pub mod share_code;
+pub mod slots;
+pub mod storage;
+pub mod sync;
+pub mod undo;
+
+pub(crate) use plugin::TrackedResourcePlugin;
+
+/// Resources and an app factory shared by the tests of tracked resources
+#[cfg(test)]
+pub(crate) mod test_utils {
+    use std::sync::{Arc, Condvar, Mutex};
+
+    use bevy::prelude::*;
+    use serde::{Deserialize, Serialize};
+
+    use crate::TrackableResource;
+
+    use super::storage::TrackedStorage;
+
+    #[derive(Debug, Default, Clone, PartialEq, Resource, Serialize, Deserialize)]
+    pub struct Coins(pub u32);
+
+    impl TrackableResource for Coins {
+        const KEY: &'static str = "coins";
+    }
+
+    #[derive(Debug, Default, Clone, PartialEq, Resource, Serialize, Deserialize)]
+    pub struct Volume(pub u32);
+
+    impl TrackableResource for Volume {
+        const KEY: &'static str = "volume";
+    }
+
+    /// Holds up work on the `IoTaskPool` until it is opened, so tests can act while the work is in flight
+    #[derive(Debug, Default, Clone)]
+    pub struct Gate(Arc<(Mutex<bool>, Condvar)>);
+
+    impl Gate {
+        pub fn open(&self) {
+            *self.0 .0.lock().unwrap() = true;
+            self.0 .1.notify_all();
+        }
+
+        pub fn close(&self) {
+            *self.0 .0.lock().unwrap() = false;
+        }
+
+        /// Block until the gate is open
+        pub fn pass(&self) {
+            let mut open = self.0 .0.lock().unwrap();
+            while !*open {
+                open = self.0 .1.wait(open).unwrap();
+            }
+        }
+    }
+
+    /// An app using `storage`, with whatever `add` adds to it, which has finished loading
+    pub fn tracked_app(storage: TrackedStorage, add: impl FnOnce(&mut App)) -> App {
+        let mut app = App::new();
+        app.insert_resource(storage);
+        add(&mut app);
+        app.finish();
+        app.cleanup();
+        app
+    }
+}
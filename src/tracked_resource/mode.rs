@@ -0,0 +1,26 @@
+use bevy::prelude::*;
+
+/// Whether tracked resources and entities are read from and written to storage.
+/// This can be changed at any time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Resource)]
+pub enum PersistenceMode {
+    /// Load values and save changes
+    #[default]
+    Normal,
+    /// Load values but never write to storage.
+    /// Changes made in this mode are not saved by themselves, but once writes are enabled again the next change saves the whole value, including them.
+    ReadOnly,
+    /// Never touch storage. Values start as their defaults and changes are not saved.
+    /// When the mode changes to one that reads storage, the stored values are loaded again.
+    Ephemeral,
+}
+
+impl PersistenceMode {
+    pub fn can_read(&self) -> bool {
+        *self != PersistenceMode::Ephemeral
+    }
+
+    pub fn can_write(&self) -> bool {
+        *self == PersistenceMode::Normal
+    }
+}
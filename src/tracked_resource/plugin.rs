@@ -0,0 +1,1136 @@
+use std::{any::type_name, marker::PhantomData};
+
+use bevy::{
+    ecs::system::{SystemParam, SystemState},
+    prelude::*,
+    utils::Instant,
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::TrackableResource;
+
+use super::{
+    commands::{
+        DeleteTrackedResource, ReloadTrackedResource, ResetAllTrackedResources,
+        ResetTrackedResource,
+    },
+    codec,
+    error::{FailurePolicy, PersistenceError, TrackedOperation, TrackedResourceError},
+    migration::{Decoded, LoadError},
+    mode::PersistenceMode,
+    recovery::{backup_key, quarantine_key},
+    registry::{LoadOutcome, TrackedResourceRegistry},
+    save_policy::{PendingSave, SaveTrackedResources},
+    slots::SaveSlot,
+    storage::{ensure_storage, StorageError, TrackedStorage},
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+use super::storage::BackgroundWrite;
+
+#[derive(Debug, Default)]
+pub (crate) struct TrackedResourcePlugin<
+    T: Resource + Serialize + DeserializeOwned + TrackableResource,
+> {
+    phantom: PhantomData<T>,
+    default_value: T
+}
+
+impl<T: Resource + Serialize + DeserializeOwned + TrackableResource>
+    TrackedResourcePlugin<T>
+{
+    pub (crate) fn new(default_value: T) -> Self { Self { phantom: PhantomData, default_value } }
+}
+
+/// The persistence state of a tracked value
+#[derive(Resource)]
+pub(crate) struct TrackedState<C: codec::TrackedCodec> {
+    pub default_value: C::Value,
+    pub context: C::Context,
+    /// The slot that the current value was loaded from and will be saved to
+    pub slot: SaveSlot,
+    pub pending: PendingSave,
+    /// Don't save the next change, because it was made by loading or deleting the stored value
+    pub skip_change: bool,
+    /// Whether storage was read when the value was loaded, which it isn't in [`PersistenceMode::Ephemeral`]
+    pub read_storage: bool,
+    #[cfg(not(target_arch = "wasm32"))]
+    pub writing: Option<BackgroundWrite>,
+}
+
+impl<C: codec::TrackedCodec> TrackedState<C> {
+    pub fn new(default_value: C::Value, context: C::Context) -> Self {
+        Self {
+            default_value,
+            context,
+            slot: SaveSlot::default(),
+            pending: PendingSave::default(),
+            skip_change: false,
+            read_storage: false,
+            #[cfg(not(target_arch = "wasm32"))]
+            writing: None,
+        }
+    }
+}
+
+/// Everything needed to load and save a tracked value
+#[derive(SystemParam)]
+pub(crate) struct Persistence<'w, C: codec::TrackedCodec> {
+    pub storage: ResMut<'w, TrackedStorage>,
+    pub state: ResMut<'w, TrackedState<C>>,
+    pub registry: ResMut<'w, TrackedResourceRegistry>,
+    pub errors: EventWriter<'w, TrackedResourceError>,
+    pub mode: Res<'w, PersistenceMode>,
+}
+
+impl<'w, C: codec::TrackedCodec> Persistence<'w, C> {
+    /// Apply the failure policy and send an error event
+    pub(crate) fn report_error(&mut self, key: String, operation: TrackedOperation, error: PersistenceError) {
+        if C::FAILURE_POLICY == FailurePolicy::Panic {
+            panic!("Failed to {operation} {} {error}", C::type_name())
+        }
+        error!("Failed to {operation} {}: {error}", C::type_name());
+
+        self.errors.send(TrackedResourceError {
+            key,
+            operation,
+            error,
+        });
+    }
+
+    /// Send an error event for a problem that was worked around, without applying the failure policy
+    fn report_warning(&mut self, key: String, operation: TrackedOperation, error: PersistenceError) {
+        warn!("Problem while trying to {operation} {}: {error}", C::type_name());
+
+        self.errors.send(TrackedResourceError {
+            key,
+            operation,
+            error,
+        });
+    }
+
+    /// Load the value stored in a slot.
+    /// If the value cannot be read it is quarantined and the last good value is used instead, or the default value if there is none.
+    pub fn load(&mut self, slot: &SaveSlot) -> C::Value {
+        let key = slot.key(C::KEY);
+        let mut size = None;
+        let can_write = self.mode.can_write();
+
+        self.state.read_storage = self.mode.can_read();
+        let stored = match self.mode.can_read() {
+            true => self.storage.get(&key),
+            false => Ok(None),
+        };
+
+        let (loaded, outcome) = match stored {
+            Ok(Some(bytes)) => match self.decode(&key, &bytes) {
+                Ok((value, outcome)) => {
+                    size = Some(bytes.len());
+                    if outcome == LoadOutcome::Loaded && can_write {
+                        if let Err(error) = self.keep_backup(&key, &bytes) {
+                            self.report_error(backup_key(&key), TrackedOperation::Save, error.into());
+                        }
+                    }
+                    (Some(value), outcome)
+                }
+                Err(error) => {
+                    let outcome = LoadOutcome::Failed(error.to_string());
+                    self.report_error(key.clone(), TrackedOperation::Load, error);
+                    if can_write {
+                        if let Err(error) = self.storage.set(&quarantine_key(&key), &bytes) {
+                            self.report_error(quarantine_key(&key), TrackedOperation::Save, error.into());
+                        }
+                    }
+                    match self.restore_backup(&key) {
+                        Ok(Some(value)) => (Some(value), LoadOutcome::RestoredBackup),
+                        Ok(None) => (None, outcome),
+                        Err(error) => {
+                            self.report_error(backup_key(&key), TrackedOperation::Load, error);
+                            (None, outcome)
+                        }
+                    }
+                }
+            },
+            Ok(None) if !self.mode.can_read() => (None, LoadOutcome::Skipped),
+            Ok(None) => (None, LoadOutcome::NotFound),
+            Err(error) => {
+                let outcome = LoadOutcome::Failed(error.to_string());
+                self.report_error(key, TrackedOperation::Load, error.into());
+                (None, outcome)
+            }
+        };
+
+        if let Some(info) = self.registry.entry_mut(C::KEY) {
+            info.last_load = Some(outcome);
+            info.serialized_size = size.or(info.serialized_size);
+        }
+
+        let mut value = loaded.unwrap_or_else(|| C::clone_value(&self.state.default_value));
+
+        C::on_loaded(&mut value);
+        value
+    }
+
+    /// Whether the value needs loading from `slot`, because it was loaded from another slot or without reading storage
+    pub fn needs_load(&self, slot: &SaveSlot) -> bool {
+        self.state.slot != *slot || (!self.state.read_storage && self.mode.can_read())
+    }
+
+    /// Read a stored value and check that it is valid, repairing it if necessary
+    fn decode(&mut self, key: &str, bytes: &[u8]) -> Result<(C::Value, LoadOutcome), PersistenceError> {
+        let Decoded {
+            mut value,
+            tampered,
+            replaced_fields,
+        } = C::decode(bytes, &self.state.default_value, &self.state.context)?;
+        let mut outcome = LoadOutcome::Loaded;
+
+        if !replaced_fields.is_empty() {
+            warn!(
+                "Used default values for fields of {} that could not be loaded: {}",
+                C::type_name(),
+                replaced_fields.join(", ")
+            );
+            outcome = LoadOutcome::Merged(replaced_fields);
+        }
+
+        if let Err(error) = C::validate(&value) {
+            C::repair(&mut value);
+            C::validate(&value)?;
+            self.report_warning(key.to_string(), TrackedOperation::Load, error.into());
+            outcome = LoadOutcome::Repaired;
+        }
+
+        if tampered {
+            self.report_warning(key.to_string(), TrackedOperation::Load, LoadError::Tampered.into());
+            outcome = LoadOutcome::Tampered;
+        }
+        Ok((value, outcome))
+    }
+
+    /// Keep a copy of a value that loaded successfully
+    fn keep_backup(&mut self, key: &str, bytes: &[u8]) -> Result<(), StorageError> {
+        let backup_key = backup_key(key);
+        if self.storage.get(&backup_key)?.as_deref() != Some(bytes) {
+            self.storage.set(&backup_key, bytes)?;
+        }
+        Ok(())
+    }
+
+    fn restore_backup(&mut self, key: &str) -> Result<Option<C::Value>, PersistenceError> {
+        match self.storage.get(&backup_key(key))? {
+            Some(bytes) => {
+                let (value, _) = self.decode(key, &bytes)?;
+                warn!("Restored {} from backup", C::type_name());
+                Ok(Some(value))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn finish_save(&mut self, key: String, result: Result<(), PersistenceError>, now: Instant) {
+        match result {
+            Ok(()) => {
+                self.state.pending.finish_save(now);
+                if let Some(info) = self.registry.entry_mut(C::KEY) {
+                    info.last_saved = Some(now);
+                }
+            }
+            Err(error) => {
+                self.state.pending.mark_failed(now, C::FAILURE_POLICY);
+                self.report_error(key, TrackedOperation::Save, error);
+            }
+        }
+    }
+
+    /// Validate and serialize the value and write it, either now or on the `IoTaskPool` if the value saves in the background
+    fn save_pending(&mut self, data: &C::Value, now: Instant, allow_background: bool) {
+        self.save_with(now, allow_background, |context| {
+            C::validate(data)?;
+            C::encode(data, context)
+        });
+    }
+
+    /// Like [`Self::save_pending`], but with the bytes returned by `encode`
+    pub fn save_with(
+        &mut self,
+        now: Instant,
+        allow_background: bool,
+        encode: impl FnOnce(&C::Context) -> Result<Vec<u8>, PersistenceError>,
+    ) {
+        if !self.mode.can_write() {
+            self.state.pending = PendingSave::default();
+            return;
+        }
+
+        let key = self.state.slot.key(C::KEY);
+        self.state.pending.begin_save();
+
+        let bytes = match encode(&self.state.context) {
+            Ok(bytes) => bytes,
+            Err(error) => return self.finish_save(key, Err(error), now),
+        };
+
+        if let Some(info) = self.registry.entry_mut(C::KEY) {
+            info.serialized_size = Some(bytes.len());
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if allow_background && C::SAVE_IN_BACKGROUND {
+            self.state.writing = Some(self.storage.set_in_background(key, bytes));
+            return;
+        }
+        #[cfg(target_arch = "wasm32")]
+        let _ = allow_background;
+
+        let result = self.storage.set(&key, &bytes).map_err(PersistenceError::from);
+        self.finish_save(key, result, now);
+    }
+
+    /// Handle the background write if it has finished.
+    /// Returns false if it is still in progress.
+    fn poll_write(&mut self, now: Instant) -> bool {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(write) = self.state.writing.take() {
+            match write.try_take() {
+                Some(result) => {
+                    let result = result.map_err(PersistenceError::from);
+                    self.finish_save(write.key, result, now);
+                }
+                None => {
+                    self.state.writing = Some(write);
+                    return false;
+                }
+            }
+        }
+        #[cfg(target_arch = "wasm32")]
+        let _ = now;
+        true
+    }
+
+    /// Block until any background write has finished
+    fn wait_for_write(&mut self) {
+        if !self.poll_write(Instant::now()) {
+            self.storage.wait_for_writes();
+            self.poll_write(Instant::now());
+        }
+    }
+
+    /// Save any unsaved changes, blocking until they have been written
+    fn flush(&mut self, data: &C::Value) {
+        self.wait_for_write();
+
+        if self.state.pending.is_dirty() {
+            self.save_pending(data, Instant::now(), false);
+        }
+    }
+
+    /// Remove the stored value and its backups, and discard any unsaved changes
+    fn delete(&mut self) {
+        self.wait_for_write();
+        self.state.pending = PendingSave::default();
+        if !self.mode.can_write() {
+            return;
+        }
+
+        let key = self.state.slot.key(C::KEY);
+        for key in [backup_key(&key), quarantine_key(&key), key] {
+            if let Err(error) = self.storage.remove(&key) {
+                self.report_error(key, TrackedOperation::Delete, error.into());
+            }
+        }
+    }
+}
+
+fn track_changes<C: codec::TrackedCodec>(data: Res<C::Value>, mut persistence: Persistence<C>)
+where
+    C::Value: Resource,
+{
+    let now = Instant::now();
+    // Changes made while writes are disabled are never saved, even if writes are enabled again before the save policy fires
+    if data.is_changed()
+        && !std::mem::take(&mut persistence.state.skip_change)
+        && persistence.mode.can_write()
+    {
+        persistence.state.pending.mark_changed(now);
+    }
+
+    // Only one write at a time so that writes to the key happen in order
+    if !persistence.poll_write(now) {
+        return;
+    }
+
+    if persistence.state.pending.should_save(C::SAVE_POLICY, now) {
+        persistence.save_pending(&data, now, true);
+    }
+}
+
+/// Save any changes that are still waiting on the save policy or being written before the app closes
+fn flush_on_exit<C: codec::TrackedCodec>(
+    exit: EventReader<AppExit>,
+    data: Res<C::Value>,
+    mut persistence: Persistence<C>,
+) where
+    C::Value: Resource,
+{
+    if !exit.is_empty() {
+        persistence.flush(&data);
+    }
+}
+
+fn handle_commands<C: codec::TrackedCodec>(
+    mut reload: EventReader<ReloadTrackedResource<C::Value>>,
+    mut reset: EventReader<ResetTrackedResource<C::Value>>,
+    mut reset_all: EventReader<ResetAllTrackedResources>,
+    mut delete: EventReader<DeleteTrackedResource<C::Value>>,
+    mut data: ResMut<C::Value>,
+    mut persistence: Persistence<C>,
+) where
+    C::Value: Resource,
+{
+    if reload.read().count() > 0 {
+        persistence.wait_for_write();
+        let slot = persistence.state.slot.clone();
+        *data = persistence.load(&slot);
+        persistence.state.pending = PendingSave::default();
+        persistence.state.skip_change = true;
+    }
+
+    if reset.read().count() + reset_all.read().count() > 0 {
+        *data = C::clone_value(&persistence.state.default_value);
+        persistence.state.skip_change = false;
+    }
+
+    if delete.read().count() > 0 {
+        persistence.delete();
+        *data = C::clone_value(&persistence.state.default_value);
+        persistence.state.skip_change = true;
+    }
+}
+
+/// When the save slot changes, save any changes to the old slot and then load from the new one.
+/// Values are also loaded again when storage can be read after being loaded in [`PersistenceMode::Ephemeral`].
+fn switch_slot<C: codec::TrackedCodec>(
+    slot: Res<SaveSlot>,
+    mut data: ResMut<C::Value>,
+    mut persistence: Persistence<C>,
+) where
+    C::Value: Resource,
+{
+    if !persistence.needs_load(&slot) {
+        return;
+    }
+
+    persistence.flush(&data);
+
+    *data = persistence.load(&slot);
+    persistence.state.slot = slot.clone();
+    persistence.state.pending = PendingSave::default();
+    persistence.state.skip_change = true;
+}
+
+/// Insert a tracked resource and add the systems that save it and handle its commands
+pub(crate) fn add_tracked_resource<C: codec::TrackedCodec>(
+    app: &mut App,
+    default_value: C::Value,
+    context: C::Context,
+) where
+    C::Value: Resource,
+{
+    app.insert_resource(C::clone_value(&default_value));
+    app.insert_resource(TrackedState::<C>::new(default_value, context));
+    app.add_event::<TrackedResourceError>();
+    app.add_event::<ResetTrackedResource<C::Value>>();
+    app.add_event::<ReloadTrackedResource<C::Value>>();
+    app.add_event::<DeleteTrackedResource<C::Value>>();
+    app.add_event::<ResetAllTrackedResources>();
+    app.add_systems(
+        PostUpdate,
+        (switch_slot::<C>, handle_commands::<C>, track_changes::<C>)
+            .chain()
+            .in_set(SaveTrackedResources),
+    );
+    app.add_systems(Last, flush_on_exit::<C>);
+}
+
+/// Load a tracked resource from the current save slot
+pub(crate) fn load_tracked_resource<C: codec::TrackedCodec>(world: &mut World)
+where
+    C::Value: Resource,
+{
+    ensure_storage(world);
+    let slot = world.get_resource_or_insert_with(SaveSlot::default).clone();
+
+    let mut system_state = SystemState::<Persistence<C>>::new(world);
+    let mut persistence = system_state.get_mut(world);
+    let value = persistence.load(&slot);
+    persistence.state.slot = slot;
+    system_state.apply(world);
+
+    world.insert_resource(value);
+}
+
+impl<T: Resource +  Serialize + DeserializeOwned + TrackableResource + Clone> Plugin
+    for TrackedResourcePlugin<T>
+{
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TrackedResourceRegistry>();
+        app.init_resource::<PersistenceMode>();
+        app.world_mut()
+            .resource_mut::<TrackedResourceRegistry>()
+            .register::<T>();
+
+        add_tracked_resource::<T>(app, self.default_value.clone(), ());
+    }
+
+    fn finish(&self, app: &mut App) {
+        load_tracked_resource::<T>(app.world_mut());
+    }
+    fn name(&self) -> &str {
+        type_name::<T>()
+    }
+
+    fn is_unique(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use bevy::utils::Duration;
+    use serde::Deserialize;
+
+    use crate::{
+        tracked_resource::{
+            commands::TrackedResourceCommands,
+            error::ValidationError,
+            migration::from_bytes,
+            save_policy::SavePolicy,
+            storage::{MemoryStorage, StorageBackend, StorageError},
+            test_utils::{tracked_app, Coins, Gate},
+        },
+        CanInitTrackedResource,
+    };
+
+    #[cfg(feature = "protection")]
+    use crate::tracked_resource::protection::{Protection, TamperPolicy};
+
+    use super::*;
+
+    fn app_with_storage(storage: MemoryStorage) -> App {
+        tracked_app(TrackedStorage::new(storage), |app| {
+            app.init_tracked_resource::<Coins>();
+        })
+    }
+
+    #[test]
+    pub fn test_changes_are_persisted() {
+        let mut app = app_with_storage(MemoryStorage::default());
+        app.world_mut().resource_mut::<Coins>().0 = 42;
+        app.update();
+
+        let stored = app.world().resource::<TrackedStorage>().get(Coins::KEY);
+        assert_eq!(
+            stored.unwrap(),
+            Some(br#"{"version":0,"data":42}"#.to_vec())
+        );
+    }
+
+    #[derive(Debug, Default, Clone, PartialEq, Resource, Serialize, Deserialize)]
+    struct Brightness(u32);
+
+    impl TrackableResource for Brightness {
+        const KEY: &'static str = "brightness";
+        const SAVE_POLICY: SavePolicy = SavePolicy::Debounced(Duration::from_secs(3600));
+    }
+
+    #[test]
+    pub fn test_pending_changes_are_flushed_on_exit() {
+        let mut app = tracked_app(TrackedStorage::new(MemoryStorage::default()), |app| {
+            app.init_tracked_resource::<Brightness>();
+        });
+
+        app.world_mut().resource_mut::<Brightness>().0 = 11;
+        app.update();
+        let storage = app.world().resource::<TrackedStorage>();
+        assert_eq!(storage.get(Brightness::KEY).unwrap(), None);
+
+        app.world_mut().send_event(AppExit::Success);
+        app.update();
+        let storage = app.world().resource::<TrackedStorage>();
+        assert_eq!(
+            storage.get(Brightness::KEY).unwrap(),
+            Some(br#"{"version":0,"data":11}"#.to_vec())
+        );
+    }
+
+    /// Storage that cannot be written to, like a full disk
+    struct ReadOnlyStorage;
+
+    impl StorageBackend for ReadOnlyStorage {
+        fn get(&self, _key: &str) -> Result<Option<Vec<u8>>, StorageError> {
+            Ok(None)
+        }
+
+        fn set(&mut self, _key: &str, _value: &[u8]) -> Result<(), StorageError> {
+            Err(StorageError::Backend("disk full".to_string()))
+        }
+
+        fn remove(&mut self, _key: &str) -> Result<(), StorageError> {
+            Err(StorageError::Backend("disk full".to_string()))
+        }
+
+        fn keys(&self) -> Result<Vec<String>, StorageError> {
+            Ok(vec![])
+        }
+    }
+
+    #[test]
+    pub fn test_save_errors_are_sent_as_events() {
+        let mut app = tracked_app(TrackedStorage::new(ReadOnlyStorage), |app| {
+            app.init_tracked_resource::<Coins>();
+        });
+
+        app.world_mut().resource_mut::<Coins>().0 = 42;
+        app.update();
+
+        let events = app.world().resource::<Events<TrackedResourceError>>();
+        let mut reader = events.get_reader();
+        let errors: Vec<_> = reader.read(events).collect();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].key, Coins::KEY);
+        assert_eq!(errors[0].operation, TrackedOperation::Save);
+    }
+
+    #[test]
+    pub fn test_switching_slot_reloads() {
+        let alice = SaveSlot::named("alice");
+        let mut storage = MemoryStorage::default();
+        storage
+            .set(&alice.key(Coins::KEY), br#"{"version":0,"data":100}"#)
+            .unwrap();
+
+        let mut app = app_with_storage(storage);
+        app.world_mut().resource_mut::<Coins>().0 = 5;
+        app.update();
+
+        *app.world_mut().resource_mut::<SaveSlot>() = alice;
+        app.update();
+        assert_eq!(app.world().resource::<Coins>(), &Coins(100));
+
+        *app.world_mut().resource_mut::<SaveSlot>() = SaveSlot::default();
+        app.update();
+        assert_eq!(app.world().resource::<Coins>(), &Coins(5));
+    }
+
+    #[test]
+    pub fn test_switching_to_empty_slot_stores_nothing() {
+        let mut app = app_with_storage(MemoryStorage::default());
+        app.update();
+
+        *app.world_mut().resource_mut::<SaveSlot>() = SaveSlot::named("empty");
+        app.update();
+        app.update();
+
+        let storage = app.world().resource::<TrackedStorage>();
+        assert_eq!(storage.slots().unwrap(), vec![]);
+    }
+
+    #[test]
+    pub fn test_corrupt_value_is_quarantined_and_backup_restored() {
+        let mut storage = MemoryStorage::default();
+        storage.set(Coins::KEY, br#"{"version":0,"data":7}"#).unwrap();
+        let mut app = app_with_storage(storage);
+        app.world_mut().resource_mut::<Coins>().0 = 8;
+        app.update();
+
+        let mut storage = app.world_mut().remove_resource::<TrackedStorage>().unwrap();
+        storage.set(Coins::KEY, b"garbage").unwrap();
+
+        let app = tracked_app(storage, |app| {
+            app.init_tracked_resource::<Coins>();
+        });
+
+        assert_eq!(app.world().resource::<Coins>(), &Coins(7));
+        let storage = app.world().resource::<TrackedStorage>();
+        assert_eq!(
+            storage.get(&quarantine_key(Coins::KEY)).unwrap(),
+            Some(b"garbage".to_vec())
+        );
+    }
+
+    #[derive(Debug, Default, Clone, PartialEq, Resource, Serialize, Deserialize)]
+    struct Progress(Vec<u32>);
+
+    impl TrackableResource for Progress {
+        const KEY: &'static str = "progress";
+        const SAVE_IN_BACKGROUND: bool = true;
+    }
+
+    /// Storage whose writes wait for a gate to open
+    struct GatedStorage {
+        gate: Gate,
+        storage: MemoryStorage,
+    }
+
+    impl StorageBackend for GatedStorage {
+        fn get(&self, key: &str) -> Result<Option<Vec<u8>>, StorageError> {
+            self.storage.get(key)
+        }
+
+        fn set(&mut self, key: &str, value: &[u8]) -> Result<(), StorageError> {
+            self.gate.pass();
+            self.storage.set(key, value)
+        }
+
+        fn remove(&mut self, key: &str) -> Result<(), StorageError> {
+            self.storage.remove(key)
+        }
+
+        fn keys(&self) -> Result<Vec<String>, StorageError> {
+            self.storage.keys()
+        }
+    }
+
+    #[test]
+    pub fn test_background_writes_are_flushed_on_exit() {
+        let gate = Gate::default();
+        let storage = GatedStorage {
+            gate: gate.clone(),
+            storage: MemoryStorage::default(),
+        };
+        let mut app = tracked_app(TrackedStorage::new(storage), |app| {
+            app.init_tracked_resource::<Progress>();
+        });
+
+        // The first write can't finish until the gate opens, so the later changes wait for it
+        for i in 0..3 {
+            app.world_mut().resource_mut::<Progress>().0.push(i);
+            app.update();
+        }
+        let state = app.world().resource::<TrackedState<Progress>>();
+        assert!(state.writing.is_some());
+        assert!(state.pending.is_dirty());
+
+        gate.open();
+        app.world_mut().send_event(AppExit::Success);
+        app.update();
+
+        let storage = app.world().resource::<TrackedStorage>();
+        storage.wait_for_writes();
+        assert_eq!(
+            storage.get(Progress::KEY).unwrap(),
+            Some(br#"{"version":0,"data":[0,1,2]}"#.to_vec())
+        );
+    }
+
+    #[test]
+    pub fn test_reset_all_and_delete() {
+        let mut app = app_with_storage(MemoryStorage::default());
+        app.world_mut().resource_mut::<Coins>().0 = 42;
+        app.update();
+
+        app.world_mut().commands().reset_all_tracked_resources();
+        app.world_mut().flush();
+        app.update();
+        assert_eq!(app.world().resource::<Coins>(), &Coins(0));
+        let storage = app.world().resource::<TrackedStorage>();
+        assert_eq!(
+            storage.get(Coins::KEY).unwrap(),
+            Some(br#"{"version":0,"data":0}"#.to_vec())
+        );
+
+        app.world_mut().resource_mut::<Coins>().0 = 42;
+        app.update();
+        app.world_mut().commands().delete_tracked_resource::<Coins>();
+        app.world_mut().flush();
+        app.update();
+        app.update();
+        assert_eq!(app.world().resource::<Coins>(), &Coins(0));
+        let storage = app.world().resource::<TrackedStorage>();
+        assert_eq!(storage.get(Coins::KEY).unwrap(), None);
+    }
+
+    #[test]
+    pub fn test_reload() {
+        let mut app = app_with_storage(MemoryStorage::default());
+        app.world_mut().resource_mut::<Coins>().0 = 42;
+        app.update();
+
+        app.world_mut()
+            .resource_mut::<TrackedStorage>()
+            .set(Coins::KEY, br#"{"version":0,"data":3}"#)
+            .unwrap();
+        app.world_mut()
+            .send_event(ReloadTrackedResource::<Coins>::default());
+        app.update();
+        assert_eq!(app.world().resource::<Coins>(), &Coins(3));
+    }
+
+    #[test]
+    pub fn test_reloaded_value_is_not_saved() {
+        let mut storage = MemoryStorage::default();
+        storage.set(Coins::KEY, br#"{"version":0,"data":7}"#).unwrap();
+        let mut app = app_with_storage(storage);
+        app.update();
+
+        app.world_mut()
+            .resource_mut::<TrackedStorage>()
+            .set(Coins::KEY, b"garbage")
+            .unwrap();
+        app.world_mut()
+            .send_event(ReloadTrackedResource::<Coins>::default());
+        app.update();
+        app.update();
+
+        assert_eq!(app.world().resource::<Coins>(), &Coins(7));
+        let storage = app.world().resource::<TrackedStorage>();
+        assert_eq!(storage.get(Coins::KEY).unwrap(), Some(b"garbage".to_vec()));
+    }
+
+    #[test]
+    pub fn test_value_is_loaded() {
+        let mut storage = MemoryStorage::default();
+        storage.set(Coins::KEY, br#"{"version":0,"data":7}"#).unwrap();
+
+        let app = app_with_storage(storage);
+
+        assert_eq!(app.world().resource::<Coins>(), &Coins(7));
+    }
+
+    #[test]
+    pub fn test_registry() {
+        let mut storage = MemoryStorage::default();
+        storage.set(Coins::KEY, br#"{"version":0,"data":7}"#).unwrap();
+        let mut app = app_with_storage(storage);
+
+        let info = app
+            .world()
+            .resource::<TrackedResourceRegistry>()
+            .get(Coins::KEY)
+            .unwrap()
+            .clone();
+        assert_eq!(info.type_name, type_name::<Coins>());
+        assert_eq!(info.last_load, Some(LoadOutcome::Loaded));
+        assert_eq!(info.serialized_size, Some(22));
+        assert_eq!(info.last_saved, None);
+
+        app.world_mut().resource_mut::<Coins>().0 = 100;
+        app.update();
+
+        let registry = app.world().resource::<TrackedResourceRegistry>();
+        assert_eq!(registry.len(), 1);
+        let info = registry.get(Coins::KEY).unwrap();
+        assert_eq!(info.serialized_size, Some(24));
+        assert!(info.last_saved.is_some());
+    }
+
+    #[cfg(feature = "protection")]
+    #[derive(Debug, Default, Clone, PartialEq, Resource, Serialize, Deserialize)]
+    struct Gold(u32);
+
+    #[cfg(feature = "protection")]
+    impl TrackableResource for Gold {
+        const KEY: &'static str = "gold";
+        const FAILURE_POLICY: FailurePolicy = FailurePolicy::Panic;
+        const PROTECTION: Protection = Protection::Signed {
+            key: b"secret",
+            on_tampered: TamperPolicy::Flag,
+        };
+    }
+
+    #[cfg(feature = "protection")]
+    #[test]
+    pub fn test_tampered_value_is_flagged() {
+        let mut storage = MemoryStorage::default();
+        storage.set(Gold::KEY, br#"{"version":0,"data":9999}"#).unwrap();
+
+        let mut app = tracked_app(TrackedStorage::new(storage), |app| {
+            app.init_tracked_resource::<Gold>();
+        });
+
+        assert_eq!(app.world().resource::<Gold>(), &Gold(9999));
+        let info = app.world().resource::<TrackedResourceRegistry>().get(Gold::KEY);
+        assert_eq!(info.unwrap().last_load, Some(LoadOutcome::Tampered));
+        let events = app.world().resource::<Events<TrackedResourceError>>();
+        let mut reader = events.get_reader();
+        let errors: Vec<_> = reader.read(events).collect();
+        assert!(matches!(
+            errors[..],
+            [TrackedResourceError {
+                error: PersistenceError::Load(LoadError::Tampered),
+                ..
+            }]
+        ));
+
+        app.world_mut().resource_mut::<Gold>().0 = 10;
+        app.update();
+
+        let storage = app.world().resource::<TrackedStorage>();
+        let stored = storage.get(Gold::KEY).unwrap().unwrap();
+        assert!(stored.starts_with(b"nbu-signed:"));
+        assert_eq!(from_bytes::<Gold>(&stored).unwrap(), Gold(10));
+    }
+
+    #[derive(Debug, Default, Clone, PartialEq, Resource, Serialize, Deserialize)]
+    struct Audio {
+        volume: f32,
+        #[serde(default)]
+        previewing: bool,
+    }
+
+    impl TrackableResource for Audio {
+        const KEY: &'static str = "audio";
+
+        fn validate(&self) -> Result<(), ValidationError> {
+            if (0.0..=1.0).contains(&self.volume) {
+                Ok(())
+            } else {
+                Err(ValidationError::new(format!("volume {}", self.volume)))
+            }
+        }
+
+        fn repair(&mut self) {
+            if self.volume <= 4.0 {
+                self.volume = self.volume.clamp(0.0, 1.0);
+            }
+        }
+
+        fn before_save(&self) -> Cow<'_, Self> {
+            Cow::Owned(Self {
+                previewing: false,
+                ..self.clone()
+            })
+        }
+    }
+
+    fn audio_app(volume: f32) -> App {
+        let mut storage = MemoryStorage::default();
+        let payload = format!(r#"{{"version":0,"data":{{"volume":{volume}}}}}"#);
+        storage.set(Audio::KEY, payload.as_bytes()).unwrap();
+
+        tracked_app(TrackedStorage::new(storage), |app| {
+            app.init_tracked_resource::<Audio>();
+        })
+    }
+
+    fn audio_outcome(app: &App) -> Option<LoadOutcome> {
+        let registry = app.world().resource::<TrackedResourceRegistry>();
+        registry.get(Audio::KEY).unwrap().last_load.clone()
+    }
+
+    #[test]
+    pub fn test_invalid_value_is_repaired() {
+        let app = audio_app(3.0);
+
+        assert_eq!(app.world().resource::<Audio>().volume, 1.0);
+        assert_eq!(audio_outcome(&app), Some(LoadOutcome::Repaired));
+        assert_eq!(
+            app.world().resource::<Events<TrackedResourceError>>().len(),
+            1
+        );
+    }
+
+    #[derive(Debug, Default, Clone, PartialEq, Resource, Serialize, Deserialize)]
+    struct Level(u32);
+
+    impl TrackableResource for Level {
+        const KEY: &'static str = "level";
+        const FAILURE_POLICY: FailurePolicy = FailurePolicy::Panic;
+
+        fn validate(&self) -> Result<(), ValidationError> {
+            match self.0 {
+                1..=10 => Ok(()),
+                level => Err(ValidationError::new(format!("level {level}"))),
+            }
+        }
+
+        fn repair(&mut self) {
+            self.0 = self.0.clamp(1, 10);
+        }
+    }
+
+    #[test]
+    pub fn test_repaired_value_does_not_panic() {
+        let mut storage = MemoryStorage::default();
+        storage.set(Level::KEY, br#"{"version":0,"data":99}"#).unwrap();
+
+        let app = tracked_app(TrackedStorage::new(storage), |app| {
+            app.insert_tracked_resource(Level(1));
+        });
+
+        assert_eq!(app.world().resource::<Level>(), &Level(10));
+        assert_eq!(
+            app.world().resource::<Events<TrackedResourceError>>().len(),
+            1
+        );
+    }
+
+    #[test]
+    pub fn test_unrepairable_value_is_replaced() {
+        let app = audio_app(400.0);
+
+        assert_eq!(app.world().resource::<Audio>(), &Audio::default());
+        assert!(matches!(audio_outcome(&app), Some(LoadOutcome::Failed(_))));
+        let storage = app.world().resource::<TrackedStorage>();
+        assert!(storage.get(&quarantine_key(Audio::KEY)).unwrap().is_some());
+    }
+
+    #[test]
+    pub fn test_before_save_and_invalid_values_are_not_saved() {
+        let mut app = audio_app(0.5);
+        *app.world_mut().resource_mut::<Audio>() = Audio {
+            volume: 0.25,
+            previewing: true,
+        };
+        app.update();
+
+        assert_eq!(
+            stored_audio(&app),
+            Audio {
+                volume: 0.25,
+                previewing: false
+            }
+        );
+
+        app.world_mut().resource_mut::<Audio>().volume = 2.0;
+        app.update();
+
+        assert_eq!(stored_audio(&app).volume, 0.25);
+    }
+
+    fn stored_audio(app: &App) -> Audio {
+        let storage = app.world().resource::<TrackedStorage>();
+        from_bytes(&storage.get(Audio::KEY).unwrap().unwrap()).unwrap()
+    }
+
+    #[test]
+    pub fn test_persistence_modes() {
+        let mut storage = MemoryStorage::default();
+        storage.set(Coins::KEY, br#"{"version":0,"data":7}"#).unwrap();
+        let mut app = tracked_app(TrackedStorage::new(storage), |app| {
+            app.insert_resource(PersistenceMode::Ephemeral);
+            app.init_tracked_resource::<Coins>();
+        });
+
+        let stored = |app: &App| {
+            let storage = app.world().resource::<TrackedStorage>();
+            from_bytes::<Coins>(&storage.get(Coins::KEY).unwrap().unwrap()).unwrap()
+        };
+
+        assert_eq!(app.world().resource::<Coins>(), &Coins(0));
+        let registry = app.world().resource::<TrackedResourceRegistry>();
+        assert_eq!(
+            registry.get(Coins::KEY).unwrap().last_load,
+            Some(LoadOutcome::Skipped)
+        );
+
+        // The stored value is loaded as soon as storage can be read
+        *app.world_mut().resource_mut::<PersistenceMode>() = PersistenceMode::ReadOnly;
+        app.update();
+        assert_eq!(app.world().resource::<Coins>(), &Coins(7));
+
+        app.world_mut().resource_mut::<Coins>().0 = 8;
+        app.update();
+        assert_eq!(stored(&app), Coins(7));
+
+        *app.world_mut().resource_mut::<PersistenceMode>() = PersistenceMode::Normal;
+        app.update();
+        assert_eq!(stored(&app), Coins(7));
+
+        app.world_mut().resource_mut::<Coins>().0 = 9;
+        app.update();
+        assert_eq!(stored(&app), Coins(9));
+    }
+
+    #[test]
+    pub fn test_leaving_ephemeral_mode_keeps_the_stored_value() {
+        let mut storage = MemoryStorage::default();
+        storage.set(Coins::KEY, br#"{"version":0,"data":7}"#).unwrap();
+        let mut app = tracked_app(TrackedStorage::new(storage), |app| {
+            app.insert_resource(PersistenceMode::Ephemeral);
+            app.init_tracked_resource::<Coins>();
+        });
+        app.world_mut().resource_mut::<Coins>().0 = 1;
+        app.update();
+
+        *app.world_mut().resource_mut::<PersistenceMode>() = PersistenceMode::Normal;
+        app.update();
+        app.update();
+        assert_eq!(app.world().resource::<Coins>(), &Coins(7));
+
+        app.world_mut().resource_mut::<Coins>().0 += 1;
+        app.update();
+        let storage = app.world().resource::<TrackedStorage>();
+        let stored = storage.get(Coins::KEY).unwrap().unwrap();
+        assert_eq!(from_bytes::<Coins>(&stored).unwrap(), Coins(8));
+    }
+
+    #[derive(Debug, Default, Clone, PartialEq, Resource, Serialize, Deserialize)]
+    struct Options {
+        music: u32,
+        sound: u32,
+    }
+
+    impl TrackableResource for Options {
+        const KEY: &'static str = "options";
+    }
+
+    #[test]
+    pub fn test_read_only_changes_are_saved_with_the_next_change() {
+        let mut app = tracked_app(TrackedStorage::new(MemoryStorage::default()), |app| {
+            app.insert_resource(PersistenceMode::ReadOnly);
+            app.init_tracked_resource::<Options>();
+        });
+
+        app.world_mut().resource_mut::<Options>().music = 3;
+        app.update();
+        *app.world_mut().resource_mut::<PersistenceMode>() = PersistenceMode::Normal;
+        app.update();
+        let storage = app.world().resource::<TrackedStorage>();
+        assert_eq!(storage.get(Options::KEY).unwrap(), None);
+
+        app.world_mut().resource_mut::<Options>().sound = 4;
+        app.update();
+        let storage = app.world().resource::<TrackedStorage>();
+        let stored = storage.get(Options::KEY).unwrap().unwrap();
+        assert_eq!(
+            from_bytes::<Options>(&stored).unwrap(),
+            Options { music: 3, sound: 4 }
+        );
+    }
+
+    #[test]
+    pub fn test_read_only_changes_are_not_saved_later() {
+        let mut app = tracked_app(TrackedStorage::new(MemoryStorage::default()), |app| {
+            app.insert_resource(PersistenceMode::ReadOnly);
+            app.init_tracked_resource::<Brightness>();
+        });
+
+        app.world_mut().resource_mut::<Brightness>().0 = 11;
+        app.update();
+
+        *app.world_mut().resource_mut::<PersistenceMode>() = PersistenceMode::Normal;
+        app.world_mut().send_event(AppExit::Success);
+        app.update();
+        let storage = app.world().resource::<TrackedStorage>();
+        assert_eq!(storage.get(Brightness::KEY).unwrap(), None);
+    }
+
+    #[derive(Debug, Default, Clone, PartialEq, Resource, Serialize, Deserialize)]
+    struct Gems(u32);
+
+    impl TrackableResource for Gems {
+        const KEY: &'static str = "coins";
+    }
+
+    #[test]
+    #[should_panic(expected = "both use the key \"coins\"")]
+    pub fn test_duplicate_keys_panic() {
+        let mut app = App::new();
+        app.init_tracked_resource::<Coins>();
+        app.init_tracked_resource::<Gems>();
+    }
+}
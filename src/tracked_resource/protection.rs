@@ -0,0 +1,197 @@
+use std::borrow::Cow;
+
+#[cfg(feature = "protection")]
+use chacha20poly1305::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    ChaCha20Poly1305, Nonce,
+};
+#[cfg(feature = "protection")]
+use hmac::{Hmac, Mac};
+#[cfg(feature = "protection")]
+use sha2::{Digest, Sha256};
+
+use super::migration::LoadError;
+
+const SIGNED_TAG: &[u8] = b"nbu-signed:";
+#[cfg(feature = "protection")]
+const ENCRYPTED_TAG: &[u8] = b"nbu-encrypted:";
+const MAC_LENGTH: usize = 32;
+#[cfg(feature = "protection")]
+const NONCE_LENGTH: usize = 12;
+
+/// How the stored values of a tracked resource are protected from being read or edited.
+///
+/// The key is compiled into the app, so this stops players casually editing their save rather than a determined attacker.
+/// Values exported in a [`super::bundle::TrackedResourceBundle`] are not protected.
+///
+/// Signing and encryption need the `protection` feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Protection {
+    #[default]
+    None,
+    /// Sign stored values with HMAC-SHA256 so that edited values are detected
+    #[cfg(feature = "protection")]
+    Signed {
+        key: &'static [u8],
+        on_tampered: TamperPolicy,
+    },
+    /// Encrypt stored values with ChaCha20-Poly1305. Edited values are detected too.
+    #[cfg(feature = "protection")]
+    Encrypted {
+        key: &'static [u8],
+        on_tampered: TamperPolicy,
+    },
+}
+
+/// What to do with a stored value that fails its integrity check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TamperPolicy {
+    /// Treat the value like any other value that cannot be read.
+    /// It is quarantined and the backup or default value is used instead.
+    #[default]
+    Reject,
+    /// Load the value anyway but send a [`super::error::TrackedResourceError`].
+    /// Encrypted values that fail to decrypt are always rejected.
+    Flag,
+}
+
+/// A stored payload with its protection removed
+pub(crate) struct Opened<'a> {
+    pub payload: Cow<'a, [u8]>,
+    /// The payload failed its integrity check but is allowed by [`TamperPolicy::Flag`]
+    pub tampered: bool,
+}
+
+impl Protection {
+    /// Protect a payload before it is stored
+    pub(crate) fn seal(&self, payload: Vec<u8>) -> Vec<u8> {
+        match self {
+            Protection::None => payload,
+            #[cfg(feature = "protection")]
+            Protection::Signed { key, .. } => {
+                let mut bytes = SIGNED_TAG.to_vec();
+                bytes.extend_from_slice(&mac(key, &payload).finalize().into_bytes());
+                bytes.extend_from_slice(&payload);
+                bytes
+            }
+            #[cfg(feature = "protection")]
+            Protection::Encrypted { key, .. } => {
+                let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+                let ciphertext = cipher(key)
+                    .encrypt(&nonce, payload.as_slice())
+                    .expect("Encrypting in memory cannot fail");
+                let mut bytes = ENCRYPTED_TAG.to_vec();
+                bytes.extend_from_slice(&nonce);
+                bytes.extend_from_slice(&ciphertext);
+                bytes
+            }
+        }
+    }
+
+    /// Check and remove the protection from a stored payload
+    pub(crate) fn open<'a>(&self, bytes: &'a [u8]) -> Result<Opened<'a>, LoadError> {
+        match self {
+            Protection::None => {
+                let payload = match bytes.strip_prefix(SIGNED_TAG) {
+                    Some(signed) if signed.len() >= MAC_LENGTH => &signed[MAC_LENGTH..],
+                    _ => bytes,
+                };
+                Ok(Opened {
+                    payload: Cow::Borrowed(payload),
+                    tampered: false,
+                })
+            }
+            #[cfg(feature = "protection")]
+            Protection::Signed { key, on_tampered } => match bytes.strip_prefix(SIGNED_TAG) {
+                Some(signed) if signed.len() >= MAC_LENGTH => {
+                    let (tag, payload) = signed.split_at(MAC_LENGTH);
+                    let tampered = mac(key, payload).verify_slice(tag).is_err();
+                    on_tampered.check(Cow::Borrowed(payload), tampered)
+                }
+                _ => on_tampered.check(Cow::Borrowed(bytes), true),
+            },
+            #[cfg(feature = "protection")]
+            Protection::Encrypted { key, on_tampered } => match bytes.strip_prefix(ENCRYPTED_TAG) {
+                Some(encrypted) if encrypted.len() >= NONCE_LENGTH => {
+                    let (nonce, ciphertext) = encrypted.split_at(NONCE_LENGTH);
+                    let payload = cipher(key)
+                        .decrypt(Nonce::from_slice(nonce), ciphertext)
+                        .map_err(|_| LoadError::Tampered)?;
+                    on_tampered.check(Cow::Owned(payload), false)
+                }
+                Some(_) => Err(LoadError::Tampered),
+                None => on_tampered.check(Cow::Borrowed(bytes), true),
+            },
+        }
+    }
+}
+
+#[cfg(feature = "protection")]
+impl TamperPolicy {
+    fn check(self, payload: Cow<'_, [u8]>, tampered: bool) -> Result<Opened<'_>, LoadError> {
+        if tampered && self == TamperPolicy::Reject {
+            return Err(LoadError::Tampered);
+        }
+        Ok(Opened { payload, tampered })
+    }
+}
+
+#[cfg(feature = "protection")]
+fn mac(key: &[u8], payload: &[u8]) -> Hmac<Sha256> {
+    let mut mac =
+        <Hmac<Sha256> as Mac>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(payload);
+    mac
+}
+
+#[cfg(feature = "protection")]
+fn cipher(key: &[u8]) -> ChaCha20Poly1305 {
+    ChaCha20Poly1305::new(&Sha256::digest(key))
+}
+
+#[cfg(all(test, feature = "protection"))]
+mod tests {
+    use super::*;
+
+    const SIGNED: Protection = Protection::Signed {
+        key: b"secret",
+        on_tampered: TamperPolicy::Reject,
+    };
+
+    const ENCRYPTED: Protection = Protection::Encrypted {
+        key: b"secret",
+        on_tampered: TamperPolicy::Flag,
+    };
+
+    #[test]
+    pub fn test_signed() {
+        let sealed = SIGNED.seal(b"{\"coins\":1}".to_vec());
+        assert_eq!(SIGNED.open(&sealed).unwrap().payload, &b"{\"coins\":1}"[..]);
+
+        let mut edited = sealed.clone();
+        let digit = edited.len() - 2;
+        edited[digit] = b'9';
+        assert!(matches!(SIGNED.open(&edited), Err(LoadError::Tampered)));
+        assert!(matches!(
+            SIGNED.open(b"{\"coins\":9}"),
+            Err(LoadError::Tampered)
+        ));
+    }
+
+    #[test]
+    pub fn test_encrypted() {
+        let sealed = ENCRYPTED.seal(b"{\"coins\":1}".to_vec());
+        assert!(!sealed.windows(5).any(|x| x == b"coins"));
+
+        let opened = ENCRYPTED.open(&sealed).unwrap();
+        assert_eq!(opened.payload, &b"{\"coins\":1}"[..]);
+        assert!(!opened.tampered);
+
+        let mut edited = sealed.clone();
+        *edited.last_mut().unwrap() ^= 1;
+        assert!(matches!(ENCRYPTED.open(&edited), Err(LoadError::Tampered)));
+
+        let plain = ENCRYPTED.open(b"{\"coins\":9}").unwrap();
+        assert!(plain.tampered);
+    }
+}
@@ -0,0 +1,17 @@
+const QUARANTINE_SUFFIX: &str = ".quarantine";
+const BACKUP_SUFFIX: &str = ".backup";
+
+/// Where an unreadable value is moved to, so it can be inspected or repaired later
+pub fn quarantine_key(key: &str) -> String {
+    format!("{key}{QUARANTINE_SUFFIX}")
+}
+
+/// Where the last value that loaded successfully is kept
+pub fn backup_key(key: &str) -> String {
+    format!("{key}{BACKUP_SUFFIX}")
+}
+
+/// Whether a storage key holds a quarantined or backup value rather than a tracked resource
+pub fn is_recovery_key(key: &str) -> bool {
+    key.ends_with(QUARANTINE_SUFFIX) || key.ends_with(BACKUP_SUFFIX)
+}
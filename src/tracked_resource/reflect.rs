@@ -0,0 +1,368 @@
+use std::{
+    any::{type_name, TypeId},
+    marker::PhantomData,
+};
+
+use bevy::{
+    prelude::*,
+    reflect::{
+        serde::{TypedReflectDeserializer, TypedReflectSerializer},
+        GetTypeRegistration, TypeRegistry,
+    },
+};
+use serde::de::{DeserializeSeed, Error};
+use serde_json::Value;
+
+use super::{
+    codec,
+    error::{FailurePolicy, PersistenceError},
+    format::{self, Compression, Format},
+    migration::{migrate_data, Decoded, LoadError, MigrationError, VersionedRef},
+    mode::PersistenceMode,
+    plugin::{add_tracked_resource, load_tracked_resource},
+    protection::Protection,
+    registry::TrackedResourceRegistry,
+    save_policy::SavePolicy,
+};
+
+/// A resource which is loaded from and saved to storage through reflection rather than serde.
+/// Useful for resources that contain types which implement [`Reflect`] but not `Serialize`.
+/// Each item works like the one with the same name on [`crate::TrackableResource`].
+///
+/// Every type inside the resource must be registered in the [`AppTypeRegistry`]. The resource itself is registered automatically.
+pub trait ReflectTrackableResource:
+    Resource + FromReflect + TypePath + GetTypeRegistration
+{
+    const KEY: &'static str;
+
+    const VERSION: u32 = 0;
+
+    /// The value is in the shape produced by [`TypedReflectSerializer`].
+    fn migrate(from_version: u32, _value: Value) -> Result<Value, MigrationError> {
+        Err(MigrationError::Missing { from_version })
+    }
+
+    const SAVE_POLICY: SavePolicy = SavePolicy::Immediate;
+
+    const SAVE_IN_BACKGROUND: bool = false;
+
+    const FAILURE_POLICY: FailurePolicy = FailurePolicy::Log;
+
+    const FORMAT: Format = Format::Json;
+
+    const COMPRESSION: Compression = Compression::None;
+
+    const PROTECTION: Protection = Protection::None;
+
+    fn on_loaded(&mut self) {}
+}
+
+/// Serialize a value through the type registry, without its version
+pub(crate) fn to_data<R: ReflectTrackableResource>(
+    value: &R,
+    registry: &TypeRegistry,
+) -> Result<Value, serde_json::Error> {
+    serde_json::to_value(TypedReflectSerializer::new(value.as_reflect(), registry))
+}
+
+/// Read a stored payload through the type registry, migrating it to [`ReflectTrackableResource::VERSION`] first if necessary
+pub(crate) fn load_payload<R: ReflectTrackableResource>(
+    payload: Value,
+    registry: &TypeRegistry,
+) -> Result<R, LoadError> {
+    let data = migrate_data(payload, R::VERSION, R::migrate)?;
+
+    let registration = registry.get(TypeId::of::<R>()).ok_or_else(|| {
+        LoadError::Deserialize(Error::custom(format!(
+            "{} is not registered",
+            type_name::<R>()
+        )))
+    })?;
+    let reflected = TypedReflectDeserializer::new(registration, registry)
+        .deserialize(data)
+        .map_err(LoadError::Deserialize)?;
+    R::from_reflect(&*reflected).ok_or_else(|| {
+        LoadError::Deserialize(Error::custom(format!(
+            "Could not make {} from the stored value",
+            type_name::<R>()
+        )))
+    })
+}
+
+/// Like [`super::migration::to_bytes`], but serialized through the type registry
+pub fn to_bytes<R: ReflectTrackableResource>(
+    value: &R,
+    registry: &TypeRegistry,
+) -> Result<Vec<u8>, PersistenceError> {
+    let data = to_data(value, registry).map_err(PersistenceError::Serialize)?;
+    let payload = R::FORMAT
+        .encode(&VersionedRef {
+            version: R::VERSION,
+            data: &data,
+        })
+        .and_then(|payload| R::COMPRESSION.compress(payload))
+        .map_err(PersistenceError::Format)?;
+    Ok(R::PROTECTION.seal(payload))
+}
+
+/// Like [`super::migration::from_bytes`], but deserialized through the type registry.
+/// Also returns whether the value failed its integrity check but was allowed by [`super::protection::TamperPolicy::Flag`].
+pub fn from_bytes<R: ReflectTrackableResource>(
+    bytes: &[u8],
+    registry: &TypeRegistry,
+) -> Result<(R, bool), LoadError> {
+    let opened = R::PROTECTION.open(bytes)?;
+    let payload = format::decode(&opened.payload).map_err(LoadError::Format)?;
+    Ok((load_payload(payload, registry)?, opened.tampered))
+}
+
+/// Copy a value without requiring `Clone`
+fn clone_value<R: FromReflect>(value: &R) -> R {
+    R::from_reflect(value.as_reflect()).expect("A value can always be made from itself")
+}
+
+/// Stores `R` through the type registry
+struct Reflected<R>(PhantomData<R>);
+
+impl<R: ReflectTrackableResource> codec::TrackedCodec for Reflected<R> {
+    type Value = R;
+    type Context = AppTypeRegistry;
+
+    const KEY: &'static str = R::KEY;
+    const SAVE_POLICY: SavePolicy = R::SAVE_POLICY;
+    const SAVE_IN_BACKGROUND: bool = R::SAVE_IN_BACKGROUND;
+    const FAILURE_POLICY: FailurePolicy = R::FAILURE_POLICY;
+
+    fn type_name() -> &'static str {
+        type_name::<R>()
+    }
+
+    fn clone_value(value: &R) -> R {
+        clone_value(value)
+    }
+
+    fn encode(value: &R, registry: &AppTypeRegistry) -> Result<Vec<u8>, PersistenceError> {
+        to_bytes(value, &registry.read())
+    }
+
+    fn decode(
+        bytes: &[u8],
+        _default: &R,
+        registry: &AppTypeRegistry,
+    ) -> Result<Decoded<R>, LoadError> {
+        let (value, tampered) = from_bytes(bytes, &registry.read())?;
+        Ok(Decoded {
+            value,
+            tampered,
+            replaced_fields: vec![],
+        })
+    }
+
+    fn on_loaded(value: &mut R) {
+        value.on_loaded();
+    }
+}
+
+pub(crate) struct ReflectTrackedResourcePlugin<R> {
+    default_value: R,
+}
+
+impl<R> ReflectTrackedResourcePlugin<R> {
+    pub fn new(default_value: R) -> Self {
+        Self { default_value }
+    }
+}
+
+impl<R: ReflectTrackableResource> Plugin for ReflectTrackedResourcePlugin<R> {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TrackedResourceRegistry>();
+        app.init_resource::<PersistenceMode>();
+        app.world_mut()
+            .resource_mut::<TrackedResourceRegistry>()
+            .register_reflect::<R>();
+        app.register_type::<R>();
+
+        let registry = app.world().resource::<AppTypeRegistry>().clone();
+        add_tracked_resource::<Reflected<R>>(app, clone_value(&self.default_value), registry);
+    }
+
+    fn finish(&self, app: &mut App) {
+        load_tracked_resource::<Reflected<R>>(app.world_mut());
+    }
+
+    fn name(&self) -> &str {
+        type_name::<Self>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::{
+        tracked_resource::{
+            bundle::TrackedResourceBundle,
+            commands::TrackedResourceCommands,
+            recovery::quarantine_key,
+            registry::LoadOutcome,
+            storage::{MemoryStorage, TrackedStorage},
+            test_utils::tracked_app,
+        },
+        CanInitTrackedResource,
+    };
+
+    use super::*;
+
+    /// A type that cannot be serialized with serde
+    #[derive(Debug, Default, Clone, PartialEq, Reflect)]
+    struct Colour {
+        red: u8,
+        green: u8,
+        blue: u8,
+    }
+
+    #[derive(Debug, Default, Clone, PartialEq, Resource, Reflect)]
+    struct Theme {
+        background: Colour,
+        names: Vec<String>,
+    }
+
+    impl ReflectTrackableResource for Theme {
+        const KEY: &'static str = "theme";
+        const VERSION: u32 = 1;
+
+        fn migrate(from_version: u32, mut value: Value) -> Result<Value, MigrationError> {
+            match from_version {
+                // v0 -> v1: `name` became `names`
+                0 => {
+                    let name = value["name"].take();
+                    Ok(json!({ "background": value["background"], "names": [name] }))
+                }
+                _ => Err(MigrationError::Missing { from_version }),
+            }
+        }
+    }
+
+    fn theme_app(storage: TrackedStorage) -> App {
+        let mut app = tracked_app(storage, |app| {
+            app.register_type::<Colour>();
+            app.init_reflect_tracked_resource::<Theme>();
+        });
+        app.update();
+        app
+    }
+
+    #[test]
+    pub fn test_reflected_resource_is_persisted() {
+        let storage = TrackedStorage::new(MemoryStorage::default());
+        let mut app = theme_app(storage.clone());
+        assert_eq!(*app.world().resource::<Theme>(), Theme::default());
+
+        let theme = Theme {
+            background: Colour {
+                red: 1,
+                green: 2,
+                blue: 3,
+            },
+            names: vec!["Dusk".to_string()],
+        };
+        *app.world_mut().resource_mut::<Theme>() = theme.clone();
+        app.update();
+
+        let stored: Value =
+            serde_json::from_slice(&storage.get(Theme::KEY).unwrap().unwrap()).unwrap();
+        assert_eq!(
+            stored,
+            json!({"version": 1, "data": {"background": {"red": 1, "green": 2, "blue": 3}, "names": ["Dusk"]}})
+        );
+
+        let app = theme_app(storage);
+        assert_eq!(*app.world().resource::<Theme>(), theme);
+    }
+
+    #[test]
+    pub fn test_reflected_resource_is_migrated() {
+        let mut storage = TrackedStorage::new(MemoryStorage::default());
+        storage
+            .set(
+                Theme::KEY,
+                br#"{"background": {"red": 9, "green": 9, "blue": 9}, "name": "Noon"}"#,
+            )
+            .unwrap();
+
+        let app = theme_app(storage);
+        let theme = app.world().resource::<Theme>();
+        assert_eq!(theme.background.red, 9);
+        assert_eq!(theme.names, ["Noon"]);
+        assert_eq!(
+            app.world()
+                .resource::<TrackedResourceRegistry>()
+                .get(Theme::KEY)
+                .unwrap()
+                .last_load,
+            Some(LoadOutcome::Loaded)
+        );
+    }
+
+    fn dusk() -> Theme {
+        Theme {
+            background: Colour {
+                red: 1,
+                green: 2,
+                blue: 3,
+            },
+            names: vec!["Dusk".to_string()],
+        }
+    }
+
+    #[test]
+    pub fn test_corrupt_reflected_resource_is_restored_from_backup() {
+        let storage = TrackedStorage::new(MemoryStorage::default());
+        let mut app = theme_app(storage.clone());
+        *app.world_mut().resource_mut::<Theme>() = dusk();
+        app.update();
+        theme_app(storage.clone());
+
+        let mut storage = storage;
+        storage.set(Theme::KEY, b"garbage").unwrap();
+        let app = theme_app(storage.clone());
+
+        assert_eq!(*app.world().resource::<Theme>(), dusk());
+        assert_eq!(
+            app.world()
+                .resource::<TrackedResourceRegistry>()
+                .get(Theme::KEY)
+                .unwrap()
+                .last_load,
+            Some(LoadOutcome::RestoredBackup)
+        );
+        assert_eq!(
+            storage.get(&quarantine_key(Theme::KEY)).unwrap(),
+            Some(b"garbage".to_vec())
+        );
+    }
+
+    #[test]
+    pub fn test_reflected_resource_is_bundled_and_reset() {
+        let mut from = theme_app(TrackedStorage::new(MemoryStorage::default()));
+        *from.world_mut().resource_mut::<Theme>() = dusk();
+        let bundle = TrackedResourceBundle::export(from.world()).unwrap();
+
+        let storage = TrackedStorage::new(MemoryStorage::default());
+        let mut to = theme_app(storage.clone());
+        bundle.import(to.world_mut()).unwrap();
+        to.update();
+        assert_eq!(*to.world().resource::<Theme>(), dusk());
+        let (stored, _) = from_bytes::<Theme>(
+            &storage.get(Theme::KEY).unwrap().unwrap(),
+            &to.world().resource::<AppTypeRegistry>().read(),
+        )
+        .unwrap();
+        assert_eq!(stored, dusk());
+
+        to.world_mut().commands().reset_tracked_resource::<Theme>();
+        to.world_mut().flush();
+        to.update();
+        assert_eq!(*to.world().resource::<Theme>(), Theme::default());
+    }
+}
@@ -0,0 +1,106 @@
+use std::{any::type_name, collections::BTreeMap};
+
+use bevy::{prelude::*, utils::Instant};
+
+use crate::TrackableResource;
+
+use super::{bundle::BundleFns, reflect::ReflectTrackableResource};
+
+/// Every tracked resource in the app, by key
+#[derive(Debug, Default, Resource)]
+pub struct TrackedResourceRegistry {
+    entries: BTreeMap<&'static str, TrackedResourceInfo>,
+    bundle_fns: BTreeMap<&'static str, BundleFns>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackedResourceInfo {
+    pub key: &'static str,
+    pub type_name: &'static str,
+    /// The size of the value when it was last loaded or saved
+    pub serialized_size: Option<usize>,
+    pub last_load: Option<LoadOutcome>,
+    pub last_saved: Option<Instant>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadOutcome {
+    /// There was no stored value so the default value was used
+    NotFound,
+    Loaded,
+    /// The stored value failed validation and was repaired
+    Repaired,
+    /// Some fields of the stored value could not be read so they were taken from the default value
+    Merged(Vec<String>),
+    /// The stored value failed its integrity check but was loaded because of [`super::protection::TamperPolicy::Flag`]
+    Tampered,
+    /// The stored value could not be read so the backup was used
+    RestoredBackup,
+    /// The stored value could not be read so the default value was used
+    Failed(String),
+    /// Storage was not read because of [`super::mode::PersistenceMode::Ephemeral`]
+    Skipped,
+}
+
+impl TrackedResourceRegistry {
+    /// Panics if a different resource is already registered with the same key
+    pub(crate) fn register<T: TrackableResource>(&mut self) {
+        self.register_key(T::KEY, type_name::<T>());
+        self.bundle_fns.insert(T::KEY, BundleFns::new::<T>());
+    }
+
+    /// Panics if a different resource is already registered with the same key
+    pub(crate) fn register_reflect<R: ReflectTrackableResource>(&mut self) {
+        self.register_key(R::KEY, type_name::<R>());
+        self.bundle_fns.insert(R::KEY, BundleFns::new_reflect::<R>());
+    }
+
+    /// Panics if `key` is already registered
+    pub(crate) fn register_key(&mut self, key: &'static str, type_name: &'static str) {
+        if let Some(existing) = self.entries.get(key) {
+            panic!(
+                "Tracked resources {} and {} both use the key {:?}. Each tracked resource needs a unique key.",
+                existing.type_name, type_name, key
+            );
+        }
+
+        self.entries.insert(
+            key,
+            TrackedResourceInfo {
+                key,
+                type_name,
+                serialized_size: None,
+                last_load: None,
+                last_saved: None,
+            },
+        );
+    }
+
+    pub(crate) fn entry_mut(&mut self, key: &str) -> Option<&mut TrackedResourceInfo> {
+        self.entries.get_mut(key)
+    }
+
+    pub(crate) fn bundle_fns(&self) -> impl Iterator<Item = (&'static str, BundleFns)> + '_ {
+        self.bundle_fns.iter().map(|(key, fns)| (*key, *fns))
+    }
+
+    pub(crate) fn bundle_fns_for(&self, key: &str) -> Option<BundleFns> {
+        self.bundle_fns.get(key).copied()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&TrackedResourceInfo> {
+        self.entries.get(key)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &TrackedResourceInfo> {
+        self.entries.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
@@ -0,0 +1,147 @@
+use bevy::{
+    prelude::SystemSet,
+    utils::{Duration, Instant},
+};
+
+use super::error::FailurePolicy;
+
+/// When changes to a tracked resource are written to storage.
+/// Regardless of the policy, unsaved changes are written when the app exits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SavePolicy {
+    /// Save in the same frame that the resource changes
+    #[default]
+    Immediate,
+    /// Save once the resource has stopped changing for this long
+    Debounced(Duration),
+    /// Save changes at most once per this duration
+    Interval(Duration),
+}
+
+/// The systems in `PostUpdate` that save tracked resources and entities.
+/// Systems that change tracked values in `PostUpdate` must run before this for the change to be saved in the same frame.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, SystemSet)]
+pub struct SaveTrackedResources;
+
+/// Tracks unsaved changes to a tracked resource
+#[derive(Debug, Default)]
+pub(crate) struct PendingSave {
+    last_changed: Option<Instant>,
+    last_saved: Option<Instant>,
+    failed_attempts: u32,
+    retry_at: Option<Instant>,
+}
+
+impl PendingSave {
+    pub fn mark_changed(&mut self, now: Instant) {
+        self.last_changed = Some(now);
+    }
+
+    /// Call when a snapshot of the value is taken to be saved.
+    /// Any changes after this will need saving again.
+    pub fn begin_save(&mut self) {
+        self.last_changed = None;
+    }
+
+    /// Call when the snapshot has been written
+    pub fn finish_save(&mut self, now: Instant) {
+        self.last_saved = Some(now);
+        self.failed_attempts = 0;
+        self.retry_at = None;
+    }
+
+    /// Either schedule another attempt or give up on the snapshot, depending on the policy
+    pub fn mark_failed(&mut self, now: Instant, policy: FailurePolicy) {
+        self.failed_attempts += 1;
+        match policy.retry_delay(self.failed_attempts) {
+            Some(delay) => {
+                self.last_changed.get_or_insert(now);
+                self.retry_at = Some(now + delay);
+            }
+            None => {
+                self.failed_attempts = 0;
+                self.retry_at = None;
+            }
+        }
+    }
+
+    /// Whether there are changes that have not been saved
+    pub fn is_dirty(&self) -> bool {
+        self.last_changed.is_some()
+    }
+
+    /// Whether the unsaved changes should be saved now
+    pub fn should_save(&self, policy: SavePolicy, now: Instant) -> bool {
+        let Some(last_changed) = self.last_changed else {
+            return false;
+        };
+
+        if let Some(retry_at) = self.retry_at {
+            return now >= retry_at;
+        }
+
+        match policy {
+            SavePolicy::Immediate => true,
+            SavePolicy::Debounced(duration) => now.duration_since(last_changed) >= duration,
+            SavePolicy::Interval(duration) => self
+                .last_saved
+                .map_or(true, |last_saved| now.duration_since(last_saved) >= duration),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_debounced() {
+        let policy = SavePolicy::Debounced(Duration::from_secs(1));
+        let start = Instant::now();
+        let mut pending = PendingSave::default();
+        assert!(!pending.should_save(policy, start));
+
+        pending.mark_changed(start);
+        assert!(!pending.should_save(policy, start + Duration::from_millis(900)));
+
+        pending.mark_changed(start + Duration::from_millis(900));
+        assert!(!pending.should_save(policy, start + Duration::from_millis(1800)));
+        assert!(pending.should_save(policy, start + Duration::from_millis(1900)));
+    }
+
+    #[test]
+    pub fn test_interval() {
+        let policy = SavePolicy::Interval(Duration::from_secs(1));
+        let start = Instant::now();
+        let mut pending = PendingSave::default();
+
+        pending.mark_changed(start);
+        assert!(pending.should_save(policy, start));
+        pending.begin_save();
+        pending.finish_save(start);
+
+        pending.mark_changed(start + Duration::from_millis(100));
+        assert!(!pending.should_save(policy, start + Duration::from_millis(900)));
+        assert!(pending.should_save(policy, start + Duration::from_millis(1000)));
+    }
+
+    #[test]
+    pub fn test_retry() {
+        let failure_policy = FailurePolicy::Retry {
+            max_attempts: 2,
+            backoff: Duration::from_secs(1),
+        };
+        let start = Instant::now();
+        let mut pending = PendingSave::default();
+
+        pending.mark_changed(start);
+        pending.begin_save();
+        pending.mark_failed(start, failure_policy);
+        assert!(!pending.should_save(SavePolicy::Immediate, start));
+        assert!(pending.should_save(SavePolicy::Immediate, start + Duration::from_secs(1)));
+
+        pending.begin_save();
+        pending.mark_failed(start + Duration::from_secs(1), failure_policy);
+        assert!(!pending.is_dirty());
+    }
+}
@@ -0,0 +1,239 @@
+use std::{
+    fmt::Display,
+    io::{Read, Write},
+};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use flate2::{read::DeflateDecoder, write::DeflateEncoder};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+use crate::TrackableResource;
+
+use super::{
+    error::ValidationError,
+    migration::{load_versioned, LoadError},
+};
+
+const SHARE_CODE_VERSION: u8 = 1;
+const COMPRESSED: u8 = 1;
+const HEADER_LENGTH: usize = 10;
+const CHECKSUM_LENGTH: usize = 4;
+/// Codes that inflate to more than this many bytes are rejected, so a malicious code can't use up all the memory
+const MAX_INFLATED_LEN: u64 = 1024 * 1024;
+
+#[derive(Debug)]
+pub enum ShareCodeError {
+    /// The code is not valid base64
+    InvalidEncoding,
+    /// The code is truncated or its checksum does not match
+    Corrupted,
+    /// The code was made by a newer version of this crate
+    UnsupportedVersion(u8),
+    /// The code inflates to more than this many bytes
+    TooLarge(u64),
+    /// The code is for a different resource
+    WrongResource,
+    /// The value could not be encoded
+    Encode(String),
+    /// The value could not be decoded, deserialized or migrated
+    Load(LoadError),
+    /// The value is not valid and could not be repaired
+    Invalid(ValidationError),
+}
+
+impl Display for ShareCodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ShareCodeError::InvalidEncoding => write!(f, "Share code contains invalid characters"),
+            ShareCodeError::Corrupted => write!(f, "Share code is corrupted"),
+            ShareCodeError::UnsupportedVersion(version) => {
+                write!(f, "Share code version {version} is not supported")
+            }
+            ShareCodeError::TooLarge(limit) => {
+                write!(f, "Share code inflates to more than {limit} bytes")
+            }
+            ShareCodeError::WrongResource => write!(f, "Share code is for a different resource"),
+            ShareCodeError::Encode(message) => write!(f, "Could not make share code: {message}"),
+            ShareCodeError::Load(e) => e.fmt(f),
+            ShareCodeError::Invalid(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for ShareCodeError {}
+
+/// The first bytes of a hash of the key, which is enough to tell resources apart
+fn key_hash(key: &str) -> [u8; 4] {
+    let hash = Sha256::digest(key.as_bytes());
+    [hash[0], hash[1], hash[2], hash[3]]
+}
+
+fn checksum(bytes: &[u8]) -> [u8; CHECKSUM_LENGTH] {
+    let hash = Sha256::digest(bytes);
+    [hash[0], hash[1], hash[2], hash[3]]
+}
+
+/// Turn a value into a short URL-safe code which can be shared and read with [`from_share_code`].
+///
+/// The code contains the resource's key and version, so codes from older versions are migrated.
+pub fn to_share_code<T: TrackableResource>(value: &T) -> Result<String, ShareCodeError> {
+    let data = serde_json::to_value(&*value.before_save())
+        .map_err(|e| ShareCodeError::Encode(e.to_string()))?;
+    let payload = rmp_serde::to_vec(&data).map_err(|e| ShareCodeError::Encode(e.to_string()))?;
+
+    let mut encoder = DeflateEncoder::new(vec![], flate2::Compression::best());
+    let compressed = encoder
+        .write_all(&payload)
+        .and_then(|()| encoder.finish())
+        .map_err(|e| ShareCodeError::Encode(e.to_string()))?;
+
+    let (flags, payload) = if compressed.len() < payload.len() {
+        (COMPRESSED, compressed)
+    } else {
+        (0, payload)
+    };
+
+    let mut bytes = vec![SHARE_CODE_VERSION, flags];
+    bytes.extend_from_slice(&key_hash(T::KEY));
+    bytes.extend_from_slice(&T::VERSION.to_le_bytes());
+    bytes.extend_from_slice(&payload);
+    bytes.extend_from_slice(&checksum(&bytes));
+
+    Ok(URL_SAFE_NO_PAD.encode(bytes))
+}
+
+/// Read a code made by [`to_share_code`]. The value is validated and repaired if necessary.
+pub fn from_share_code<T: TrackableResource>(code: &str) -> Result<T, ShareCodeError> {
+    let bytes = URL_SAFE_NO_PAD
+        .decode(code.trim())
+        .map_err(|_| ShareCodeError::InvalidEncoding)?;
+
+    if bytes.len() < HEADER_LENGTH + CHECKSUM_LENGTH {
+        return Err(ShareCodeError::Corrupted);
+    }
+    let (bytes, expected) = bytes.split_at(bytes.len() - CHECKSUM_LENGTH);
+    if checksum(bytes) != expected {
+        return Err(ShareCodeError::Corrupted);
+    }
+
+    let (header, payload) = bytes.split_at(HEADER_LENGTH);
+    if header[0] != SHARE_CODE_VERSION {
+        return Err(ShareCodeError::UnsupportedVersion(header[0]));
+    }
+    if header[2..6] != key_hash(T::KEY) {
+        return Err(ShareCodeError::WrongResource);
+    }
+    let version = u32::from_le_bytes([header[6], header[7], header[8], header[9]]);
+
+    let mut inflated = vec![];
+    let payload = if header[1] & COMPRESSED != 0 {
+        DeflateDecoder::new(payload)
+            .take(MAX_INFLATED_LEN + 1)
+            .read_to_end(&mut inflated)
+            .map_err(|_| ShareCodeError::Corrupted)?;
+        if inflated.len() as u64 > MAX_INFLATED_LEN {
+            return Err(ShareCodeError::TooLarge(MAX_INFLATED_LEN));
+        }
+        &inflated
+    } else {
+        payload
+    };
+    let data: Value = rmp_serde::from_slice(payload).map_err(|_| ShareCodeError::Corrupted)?;
+
+    let mut value: T = load_versioned(json!({ "version": version, "data": data }))
+        .map_err(ShareCodeError::Load)?;
+    if value.validate().is_err() {
+        value.repair();
+        value.validate().map_err(ShareCodeError::Invalid)?;
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::Resource;
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq, Resource, Serialize, Deserialize)]
+    struct Level {
+        name: String,
+        walls: Vec<(u8, u8)>,
+    }
+
+    impl TrackableResource for Level {
+        const KEY: &'static str = "level";
+    }
+
+    #[derive(Debug, Clone, PartialEq, Resource, Serialize, Deserialize)]
+    struct Loadout(Vec<String>);
+
+    impl TrackableResource for Loadout {
+        const KEY: &'static str = "loadout";
+    }
+
+    fn level() -> Level {
+        Level {
+            name: "Spiral".to_string(),
+            walls: (0..20).map(|x| (x, x)).collect(),
+        }
+    }
+
+    #[test]
+    pub fn test_share_code_round_trips() {
+        let code = to_share_code(&level()).unwrap();
+
+        assert!(code
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+        assert_eq!(from_share_code::<Level>(&code).unwrap(), level());
+    }
+
+    #[test]
+    pub fn test_share_code_errors() {
+        let code = to_share_code(&level()).unwrap();
+
+        assert!(matches!(
+            from_share_code::<Loadout>(&code),
+            Err(ShareCodeError::WrongResource)
+        ));
+        let bytes = URL_SAFE_NO_PAD.decode(&code).unwrap();
+        let truncated = URL_SAFE_NO_PAD.encode(&bytes[..bytes.len() - 3]);
+        assert!(matches!(
+            from_share_code::<Level>(&truncated),
+            Err(ShareCodeError::Corrupted)
+        ));
+        assert!(matches!(
+            from_share_code::<Level>("not a code!"),
+            Err(ShareCodeError::InvalidEncoding)
+        ));
+
+        let mut edited = code.into_bytes();
+        edited[8] = if edited[8] == b'A' { b'B' } else { b'A' };
+        assert!(matches!(
+            from_share_code::<Level>(std::str::from_utf8(&edited).unwrap()),
+            Err(ShareCodeError::Corrupted)
+        ));
+    }
+
+    #[test]
+    pub fn test_share_code_inflated_size_is_limited() {
+        let mut encoder = DeflateEncoder::new(vec![], flate2::Compression::best());
+        encoder.write_all(&[0; 2 * 1024 * 1024]).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let mut bytes = vec![SHARE_CODE_VERSION, COMPRESSED];
+        bytes.extend_from_slice(&key_hash(Level::KEY));
+        bytes.extend_from_slice(&Level::VERSION.to_le_bytes());
+        bytes.extend_from_slice(&compressed);
+        bytes.extend_from_slice(&checksum(&bytes));
+        let code = URL_SAFE_NO_PAD.encode(bytes);
+
+        assert!(matches!(
+            from_share_code::<Level>(&code),
+            Err(ShareCodeError::TooLarge(MAX_INFLATED_LEN))
+        ));
+    }
+}
@@ -0,0 +1,150 @@
+use bevy::prelude::*;
+
+use super::storage::{decode_file_name, encode_file_name, StorageError, TrackedStorage};
+
+const SLOT_PREFIX: &str = "slot/";
+
+/// The save slot or profile that tracked resources are loaded from and saved to.
+/// Changing this resource reloads every tracked resource from the new slot.
+///
+/// The default slot stores values directly under their keys.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default, Resource)]
+pub struct SaveSlot(Option<String>);
+
+impl SaveSlot {
+    /// A named slot. Any name can be used, it is percent-encoded in storage keys.
+    pub fn named(name: impl Into<String>) -> Self {
+        Self(Some(name.into()))
+    }
+
+    /// The name of this slot, or `None` for the default slot
+    pub fn name(&self) -> Option<&str> {
+        self.0.as_deref()
+    }
+
+    /// The storage key for a tracked resource key in this slot
+    pub fn key(&self, key: &str) -> String {
+        match &self.0 {
+            Some(name) => format!("{SLOT_PREFIX}{}/{key}", encode_file_name(name)),
+            None => key.to_string(),
+        }
+    }
+
+    /// The tracked resource key for a storage key, if the storage key belongs to this slot
+    pub fn strip_key<'a>(&self, storage_key: &'a str) -> Option<&'a str> {
+        match &self.0 {
+            Some(name) => storage_key
+                .strip_prefix(SLOT_PREFIX)?
+                .strip_prefix(encode_file_name(name).as_str())?
+                .strip_prefix('/'),
+            None if storage_key.starts_with(SLOT_PREFIX) => None,
+            None => Some(storage_key),
+        }
+    }
+}
+
+impl TrackedStorage {
+    /// All named slots that have at least one stored value
+    pub fn slots(&self) -> Result<Vec<SaveSlot>, StorageError> {
+        let mut names: Vec<String> = vec![];
+        for key in self.keys()? {
+            if let Some(name) = key
+                .strip_prefix(SLOT_PREFIX)
+                .and_then(|x| x.split_once('/'))
+                .and_then(|(name, _)| decode_file_name(name))
+            {
+                names.push(name);
+            }
+        }
+        names.sort();
+        names.dedup();
+        Ok(names.into_iter().map(SaveSlot::named).collect())
+    }
+
+    /// The tracked resource keys stored in a slot
+    pub fn slot_keys(&self, slot: &SaveSlot) -> Result<Vec<String>, StorageError> {
+        Ok(self
+            .keys()?
+            .iter()
+            .filter_map(|x| slot.strip_key(x))
+            .map(|x| x.to_string())
+            .collect())
+    }
+
+    /// Copy every value in `from` to `to`, replacing values already in `to`
+    pub fn copy_slot(&mut self, from: &SaveSlot, to: &SaveSlot) -> Result<(), StorageError> {
+        if from == to {
+            return Ok(());
+        }
+        for key in self.slot_keys(from)? {
+            if let Some(value) = self.get(&from.key(&key))? {
+                self.set(&to.key(&key), &value)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove every value in a slot.
+    /// If this is the current slot, tracked resources will still be saved when they next change.
+    pub fn delete_slot(&mut self, slot: &SaveSlot) -> Result<(), StorageError> {
+        for key in self.slot_keys(slot)? {
+            self.remove(&slot.key(&key))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tracked_resource::storage::MemoryStorage;
+
+    use super::*;
+
+    #[test]
+    pub fn test_keys() {
+        let alice = SaveSlot::named("alice");
+        assert_eq!(SaveSlot::default().key("settings"), "settings");
+        assert_eq!(alice.key("settings"), "slot/alice/settings");
+
+        assert_eq!(alice.strip_key("slot/alice/settings"), Some("settings"));
+        assert_eq!(alice.strip_key("slot/alicia/settings"), None);
+        assert_eq!(SaveSlot::default().strip_key("slot/alice/settings"), None);
+        assert_eq!(SaveSlot::default().strip_key("settings"), Some("settings"));
+
+        let typed = SaveSlot::named("Bob's save/2");
+        assert_eq!(typed.key("settings"), "slot/Bob%27s%20save%2F2/settings");
+        assert_eq!(typed.strip_key(&typed.key("settings")), Some("settings"));
+        assert_eq!(typed.name(), Some("Bob's save/2"));
+    }
+
+    #[test]
+    pub fn test_list_copy_and_delete() {
+        let alice = SaveSlot::named("alice");
+        let bob = SaveSlot::named("bob");
+        let mut storage = TrackedStorage::new(MemoryStorage::default());
+        storage.set("settings", b"default").unwrap();
+        storage.set(&alice.key("settings"), b"alice").unwrap();
+        storage.set(&alice.key("coins"), b"1").unwrap();
+
+        assert_eq!(storage.slots().unwrap(), vec![alice.clone()]);
+
+        let typed = SaveSlot::named("ünï/cödé");
+        storage.copy_slot(&alice, &typed).unwrap();
+        assert_eq!(storage.slots().unwrap(), vec![alice.clone(), typed.clone()]);
+        storage.delete_slot(&typed).unwrap();
+
+        storage.copy_slot(&alice, &bob).unwrap();
+        assert_eq!(storage.slots().unwrap(), vec![alice.clone(), bob.clone()]);
+        assert_eq!(
+            storage.get(&bob.key("settings")).unwrap(),
+            Some(b"alice".to_vec())
+        );
+
+        storage.delete_slot(&alice).unwrap();
+        assert_eq!(storage.slots().unwrap(), vec![bob.clone()]);
+        assert_eq!(
+            storage.slot_keys(&SaveSlot::default()).unwrap(),
+            vec!["settings".to_string()]
+        );
+    }
+}
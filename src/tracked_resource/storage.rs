@@ -0,0 +1,534 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    path::PathBuf,
+    sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError},
+};
+
+use bevy::prelude::*;
+#[cfg(not(target_arch = "wasm32"))]
+use bevy::tasks::{IoTaskPool, TaskPool};
+
+/// Somewhere that tracked resources can be persisted to
+pub trait StorageBackend: Send + Sync + 'static {
+    /// Get the bytes stored under `key`, or `None` if nothing is stored there
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>, StorageError>;
+
+    /// Like [`StorageBackend::get`], for backends that need to update their own bookkeeping when a value is read.
+    /// Tracked resources read values through this.
+    fn read(&mut self, key: &str) -> Result<Option<Vec<u8>>, StorageError> {
+        self.get(key)
+    }
+
+    /// Store `value` under `key`, replacing anything already there
+    fn set(&mut self, key: &str, value: &[u8]) -> Result<(), StorageError>;
+
+    /// Remove the value stored under `key`. Removing a missing key is not an error.
+    fn remove(&mut self, key: &str) -> Result<(), StorageError>;
+
+    /// List every key that currently has a value
+    fn keys(&self) -> Result<Vec<String>, StorageError>;
+}
+
+#[derive(Debug)]
+pub enum StorageError {
+    Io(std::io::Error),
+    Backend(String),
+}
+
+impl Display for StorageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StorageError::Io(e) => e.fmt(f),
+            StorageError::Backend(message) => message.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for StorageError {}
+
+impl From<std::io::Error> for StorageError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+/// The storage backend used by all tracked resources.
+/// Insert this before the app runs to choose where tracked resources are persisted.
+///
+/// This is a cheap handle to the backend so it can be cloned and used from background tasks.
+#[derive(Resource, Clone)]
+pub struct TrackedStorage {
+    backend: Arc<Mutex<dyn StorageBackend>>,
+    writes_in_flight: Arc<(Mutex<usize>, Condvar)>,
+}
+
+impl TrackedStorage {
+    pub fn new(backend: impl StorageBackend) -> Self {
+        Self {
+            backend: Arc::new(Mutex::new(backend)),
+            writes_in_flight: Default::default(),
+        }
+    }
+
+    fn backend(&self) -> MutexGuard<'_, dyn StorageBackend + 'static> {
+        self.backend.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Get the bytes stored under `key`, or `None` if nothing is stored there
+    pub fn get(&self, key: &str) -> Result<Option<Vec<u8>>, StorageError> {
+        self.backend().read(key)
+    }
+
+    /// Store `value` under `key`, replacing anything already there
+    pub fn set(&mut self, key: &str, value: &[u8]) -> Result<(), StorageError> {
+        self.backend().set(key, value)
+    }
+
+    /// Remove the value stored under `key`
+    pub fn remove(&mut self, key: &str) -> Result<(), StorageError> {
+        self.backend().remove(key)
+    }
+
+    /// List every key that currently has a value
+    pub fn keys(&self) -> Result<Vec<String>, StorageError> {
+        self.backend().keys()
+    }
+
+    /// Store `value` under `key` on the [`IoTaskPool`]
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn set_in_background(&self, key: String, value: Vec<u8>) -> BackgroundWrite {
+        let write = BackgroundWrite {
+            key,
+            result: Default::default(),
+        };
+        let storage = self.clone();
+        let task_write = write.clone();
+        *self.lock_writes_in_flight() += 1;
+
+        IoTaskPool::get_or_init(TaskPool::new)
+            .spawn(async move {
+                let result = storage.backend().set(&task_write.key, &value);
+                *task_write.lock_result() = Some(result);
+                *storage.lock_writes_in_flight() -= 1;
+                storage.writes_in_flight.1.notify_all();
+            })
+            .detach();
+
+        write
+    }
+
+    fn lock_writes_in_flight(&self) -> MutexGuard<'_, usize> {
+        self.writes_in_flight
+            .0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Block until every background write has finished.
+    /// Tracked resources do this automatically when the app exits.
+    pub fn wait_for_writes(&self) {
+        let mut in_flight = self.lock_writes_in_flight();
+        while *in_flight > 0 {
+            in_flight = self
+                .writes_in_flight
+                .1
+                .wait(in_flight)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+}
+
+/// A write happening on the [`IoTaskPool`]
+#[derive(Debug, Clone)]
+pub(crate) struct BackgroundWrite {
+    pub key: String,
+    result: Arc<Mutex<Option<Result<(), StorageError>>>>,
+}
+
+impl BackgroundWrite {
+    fn lock_result(&self) -> MutexGuard<'_, Option<Result<(), StorageError>>> {
+        self.result.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// The result of the write if it has finished
+    pub fn try_take(&self) -> Option<Result<(), StorageError>> {
+        self.lock_result().take()
+    }
+}
+
+/// Make sure there is a [`TrackedStorage`].
+/// If there is none, a `PkvStore` resource is used, otherwise values are only kept in memory.
+pub(crate) fn ensure_storage(world: &mut World) {
+    if world.contains_resource::<TrackedStorage>() {
+        return;
+    }
+
+    // Apps written before `TrackedStorage` existed keep their saved values, but the `PkvStore` can't be shared so it stops being a resource
+    #[cfg(feature = "bevy_pkv")]
+    if let Some(pkv) = world.remove_resource::<bevy_pkv::PkvStore>() {
+        warn!("Tracking resources with the PkvStore resource is deprecated. It has been moved into the TrackedStorage and is no longer a resource. Insert `TrackedStorage::new(PkvStore::new(..))` instead.");
+        world.insert_resource(TrackedStorage::new(pkv));
+        return;
+    }
+
+    warn!("No TrackedStorage was added. Tracked resources will not be persisted.");
+    world.insert_resource(TrackedStorage::new(MemoryStorage::default()));
+}
+
+/// Keeps values in memory. Nothing survives the app closing.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct MemoryStorage {
+    pub values: BTreeMap<String, Vec<u8>>,
+}
+
+impl StorageBackend for MemoryStorage {
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>, StorageError> {
+        Ok(self.values.get(key).cloned())
+    }
+
+    fn set(&mut self, key: &str, value: &[u8]) -> Result<(), StorageError> {
+        self.values.insert(key.to_string(), value.to_vec());
+        Ok(())
+    }
+
+    fn remove(&mut self, key: &str) -> Result<(), StorageError> {
+        self.values.remove(key);
+        Ok(())
+    }
+
+    fn keys(&self) -> Result<Vec<String>, StorageError> {
+        Ok(self.values.keys().cloned().collect())
+    }
+}
+
+/// Stores each value in its own file in a directory
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileStorage {
+    directory: PathBuf,
+}
+
+impl FileStorage {
+    /// The directory will be created when the first value is stored
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+        }
+    }
+
+    pub fn directory(&self) -> &std::path::Path {
+        &self.directory
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.directory.join(encode_file_name(key))
+    }
+}
+
+impl StorageBackend for FileStorage {
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>, StorageError> {
+        match std::fs::read(self.path(key)) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn set(&mut self, key: &str, value: &[u8]) -> Result<(), StorageError> {
+        std::fs::create_dir_all(&self.directory)?;
+        let path = self.path(key);
+        // Write to a temporary file first so a crash never leaves a half written value
+        let temp_path = path.with_extension("tmp");
+        std::fs::write(&temp_path, value)?;
+        std::fs::rename(temp_path, path)?;
+        Ok(())
+    }
+
+    fn remove(&mut self, key: &str) -> Result<(), StorageError> {
+        match std::fs::remove_file(self.path(key)) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn keys(&self) -> Result<Vec<String>, StorageError> {
+        let entries = match std::fs::read_dir(&self.directory) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut keys = vec![];
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_some_and(|x| x == "tmp") {
+                continue;
+            }
+            if let Some(key) = path
+                .file_name()
+                .and_then(|x| x.to_str())
+                .and_then(decode_file_name)
+            {
+                keys.push(key);
+            }
+        }
+        keys.sort();
+        Ok(keys)
+    }
+}
+
+/// Percent-encode everything except ascii alphanumerics, `-` and `_` so any key is a valid file name
+pub(crate) fn encode_file_name(key: &str) -> String {
+    let mut name = String::with_capacity(key.len());
+    for byte in key.bytes() {
+        if byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_' {
+            name.push(byte as char);
+        } else {
+            name.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    name
+}
+
+pub(crate) fn decode_file_name(name: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(name.len());
+    let mut iter = name.bytes();
+    while let Some(byte) = iter.next() {
+        if byte == b'%' {
+            let hex = [iter.next()?, iter.next()?];
+            let hex = std::str::from_utf8(&hex).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+        } else {
+            bytes.push(byte);
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(feature = "bevy_pkv")]
+mod pkv {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use bevy_pkv::{GetError, PkvStore};
+
+    use super::*;
+
+    /// `PkvStore` cannot list or remove keys so we keep our own index of keys
+    const KEY_INDEX: &str = "nice_bevy_utils.keys";
+
+    /// Values are stored as this prefix followed by the base64 encoded bytes.
+    /// The prefix versions the encoding and tells values apart from ones written by `PkvStore::set` directly.
+    const ENCODING_PREFIX: &str = "nbu-bytes-v1:";
+
+    /// Stored in place of removed values
+    const REMOVED: &str = "nbu-removed";
+
+    fn set_error(e: bevy_pkv::SetError) -> StorageError {
+        StorageError::Backend(e.to_string())
+    }
+
+    fn backend_error(e: impl Display) -> StorageError {
+        StorageError::Backend(e.to_string())
+    }
+
+    /// Values stored before `StorageBackend` existed were serialized by `PkvStore` directly
+    fn legacy_value(value: &serde_json::Value) -> Result<Option<Vec<u8>>, StorageError> {
+        serde_json::to_vec(value).map(Some).map_err(backend_error)
+    }
+
+    /// Whether the value under `key` was stored before `StorageBackend` existed, so it is missing from the key index
+    fn is_legacy(pkv: &PkvStore, key: &str) -> bool {
+        match PkvStore::get::<String>(pkv, key) {
+            Ok(text) => text != REMOVED && !text.starts_with(ENCODING_PREFIX),
+            Err(GetError::NotFound) => false,
+            Err(_) => true,
+        }
+    }
+
+    fn update_index(pkv: &mut PkvStore, key: &str, present: bool) -> Result<(), StorageError> {
+        let mut keys = StorageBackend::keys(pkv)?;
+        match (keys.binary_search_by(|x| x.as_str().cmp(key)), present) {
+            (Ok(_), true) | (Err(_), false) => return Ok(()),
+            (Ok(index), false) => {
+                keys.remove(index);
+            }
+            (Err(index), true) => keys.insert(index, key.to_string()),
+        }
+        pkv.set(KEY_INDEX, &keys).map_err(set_error)
+    }
+
+    impl StorageBackend for PkvStore {
+        fn get(&self, key: &str) -> Result<Option<Vec<u8>>, StorageError> {
+            match PkvStore::get::<String>(self, key) {
+                Ok(text) if text == REMOVED => Ok(None),
+                Ok(text) => match text.strip_prefix(ENCODING_PREFIX) {
+                    Some(encoded) => STANDARD.decode(encoded).map(Some).map_err(backend_error),
+                    None => legacy_value(&serde_json::Value::String(text)),
+                },
+                Err(GetError::NotFound) => Ok(None),
+                Err(_) => match PkvStore::get::<serde_json::Value>(self, key) {
+                    Ok(value) => legacy_value(&value),
+                    Err(e) => Err(backend_error(e)),
+                },
+            }
+        }
+
+        fn read(&mut self, key: &str) -> Result<Option<Vec<u8>>, StorageError> {
+            let value = StorageBackend::get(self, key)?;
+            if value.is_some() && is_legacy(self, key) {
+                update_index(self, key, true)?;
+            }
+            Ok(value)
+        }
+
+        fn set(&mut self, key: &str, value: &[u8]) -> Result<(), StorageError> {
+            let encoded = format!("{ENCODING_PREFIX}{}", STANDARD.encode(value));
+            PkvStore::set_string(self, key, &encoded).map_err(set_error)?;
+            update_index(self, key, true)
+        }
+
+        fn remove(&mut self, key: &str) -> Result<(), StorageError> {
+            PkvStore::set_string(self, key, REMOVED).map_err(set_error)?;
+            update_index(self, key, false)
+        }
+
+        fn keys(&self) -> Result<Vec<String>, StorageError> {
+            match PkvStore::get::<Vec<String>>(self, KEY_INDEX) {
+                Ok(keys) => Ok(keys),
+                Err(GetError::NotFound) => Ok(vec![]),
+                Err(e) => Err(backend_error(e)),
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use serde::Serialize;
+
+        use crate::{
+            tracked_resource::{migration::from_bytes, test_utils::Coins},
+            CanInitTrackedResource, TrackableResource,
+        };
+
+        use super::*;
+
+        #[derive(Serialize)]
+        struct Legacy {
+            coins: u32,
+        }
+
+        #[test]
+        pub fn test_pkv_storage() {
+            let directory = std::env::temp_dir().join(format!(
+                "nice_bevy_utils_test_pkv_storage_{}",
+                std::process::id()
+            ));
+            let mut pkv = PkvStore::new_in_dir(&directory);
+
+            StorageBackend::set(&mut pkv, "bytes", &[0, 1, 2]).unwrap();
+            StorageBackend::set(&mut pkv, "empty", &[]).unwrap();
+            assert_eq!(
+                StorageBackend::get(&pkv, "bytes").unwrap(),
+                Some(vec![0, 1, 2])
+            );
+            assert_eq!(StorageBackend::get(&pkv, "empty").unwrap(), Some(vec![]));
+            assert_eq!(StorageBackend::keys(&pkv).unwrap(), ["bytes", "empty"]);
+
+            StorageBackend::remove(&mut pkv, "bytes").unwrap();
+            assert_eq!(StorageBackend::get(&pkv, "bytes").unwrap(), None);
+            assert_eq!(StorageBackend::keys(&pkv).unwrap(), ["empty"]);
+
+            pkv.set("legacy", &Legacy { coins: 3 }).unwrap();
+            pkv.set("legacy_list", &Vec::<u8>::new()).unwrap();
+            pkv.set("legacy_name", &"nbu").unwrap();
+            let get = |key| StorageBackend::get(&pkv, key).unwrap().unwrap();
+            let legacy: serde_json::Value = serde_json::from_slice(&get("legacy")).unwrap();
+            assert_eq!(legacy["coins"], 3);
+            assert_eq!(get("legacy_list"), b"[]");
+            assert_eq!(get("legacy_name"), br#""nbu""#);
+            assert_eq!(StorageBackend::keys(&pkv).unwrap(), ["empty"]);
+
+            let mut storage = TrackedStorage::new(pkv);
+            storage.get("legacy").unwrap();
+            storage.get("legacy_name").unwrap();
+            assert_eq!(storage.keys().unwrap(), ["empty", "legacy", "legacy_name"]);
+            storage.remove("legacy").unwrap();
+            assert_eq!(storage.get("legacy").unwrap(), None);
+            assert_eq!(storage.keys().unwrap(), ["empty", "legacy_name"]);
+
+            drop(storage);
+            std::fs::remove_dir_all(directory).unwrap();
+        }
+
+        #[test]
+        pub fn test_pkv_store_resource_is_moved_into_storage() {
+            let directory = std::env::temp_dir().join(format!(
+                "nice_bevy_utils_test_pkv_store_resource_{}",
+                std::process::id()
+            ));
+            let mut pkv = PkvStore::new_in_dir(&directory);
+            pkv.set(Coins::KEY, &Coins(4)).unwrap();
+
+            let mut app = App::new();
+            app.insert_resource(pkv);
+            app.init_tracked_resource::<Coins>();
+            app.finish();
+            app.cleanup();
+            assert!(!app.world().contains_resource::<PkvStore>());
+            assert_eq!(*app.world().resource::<Coins>(), Coins(4));
+
+            app.world_mut().resource_mut::<Coins>().0 = 5;
+            app.update();
+            let storage = app.world().resource::<TrackedStorage>();
+            let stored = storage.get(Coins::KEY).unwrap().unwrap();
+            assert_eq!(from_bytes::<Coins>(&stored).unwrap(), Coins(5));
+            assert!(storage.keys().unwrap().contains(&Coins::KEY.to_string()));
+
+            drop(app);
+            std::fs::remove_dir_all(directory).unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_file_name_round_trip() {
+        for key in ["settings", "my_game::Settings", "a/b c%d", "ünïcödé"] {
+            let name = encode_file_name(key);
+            assert!(name
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_' || b == b'%'));
+            assert_eq!(decode_file_name(&name).as_deref(), Some(key));
+        }
+    }
+
+    #[test]
+    pub fn test_file_storage() {
+        let directory = std::env::temp_dir().join(format!(
+            "nice_bevy_utils_test_file_storage_{}",
+            std::process::id()
+        ));
+        let mut storage = FileStorage::new(&directory);
+
+        assert_eq!(storage.get("a::b").unwrap(), None);
+        assert_eq!(storage.keys().unwrap(), Vec::<String>::new());
+
+        storage.set("a::b", b"hello").unwrap();
+        storage.set("c", b"world").unwrap();
+        assert_eq!(storage.get("a::b").unwrap(), Some(b"hello".to_vec()));
+        assert_eq!(
+            storage.keys().unwrap(),
+            vec!["a::b".to_string(), "c".to_string()]
+        );
+
+        storage.remove("a::b").unwrap();
+        storage.remove("a::b").unwrap();
+        assert_eq!(storage.get("a::b").unwrap(), None);
+        assert_eq!(storage.keys().unwrap(), vec!["c".to_string()]);
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,909 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    marker::PhantomData,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+use bevy::{
+    prelude::*,
+    tasks::{IoTaskPool, TaskPool},
+    utils::SystemTime,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::TrackableResource;
+
+use super::{
+    error::{PersistenceError, TrackedOperation, ValidationError},
+    migration::{load_versioned, LoadError},
+    plugin::Persistence,
+};
+
+const SYNC_SUFFIX: &str = ".sync";
+/// How many times to resolve a conflict before giving up, in case other devices keep pushing
+const MAX_ATTEMPTS: u32 = 3;
+
+/// Where the sync state of a stored value is kept
+pub fn sync_key(key: &str) -> String {
+    format!("{key}{SYNC_SUFFIX}")
+}
+
+/// The number of changes each device has made to a value.
+/// Comparing clocks tells whether one value was based on another or whether they were changed independently.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct VectorClock(BTreeMap<String, u64>);
+
+/// How two vector clocks are related
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClockOrdering {
+    Equal,
+    /// Every change in this clock is also in the other
+    Before,
+    /// Every change in the other clock is also in this one
+    After,
+    /// Each clock has changes the other does not
+    Concurrent,
+}
+
+impl VectorClock {
+    /// The number of changes made by a device
+    pub fn get(&self, device: &str) -> u64 {
+        self.0.get(device).copied().unwrap_or_default()
+    }
+
+    /// Record a change made by a device
+    pub fn increment(&mut self, device: &str) {
+        *self.0.entry(device.to_string()).or_default() += 1;
+    }
+
+    /// Include every change recorded in `other`
+    pub fn merge(&mut self, other: &Self) {
+        for (device, count) in other.0.iter() {
+            let entry = self.0.entry(device.clone()).or_default();
+            *entry = (*entry).max(*count);
+        }
+    }
+
+    pub fn compare(&self, other: &Self) -> ClockOrdering {
+        let devices = self.0.keys().chain(other.0.keys());
+        let (mut ahead, mut behind) = (false, false);
+        for device in devices {
+            match self.get(device).cmp(&other.get(device)) {
+                std::cmp::Ordering::Less => behind = true,
+                std::cmp::Ordering::Equal => {}
+                std::cmp::Ordering::Greater => ahead = true,
+            }
+        }
+
+        match (ahead, behind) {
+            (false, false) => ClockOrdering::Equal,
+            (false, true) => ClockOrdering::Before,
+            (true, false) => ClockOrdering::After,
+            (true, true) => ClockOrdering::Concurrent,
+        }
+    }
+}
+
+/// A value as it is sent to and received from the sync server.
+/// The data is plain JSON, so [`crate::TrackableResource::PROTECTION`] does not apply to it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SyncedValue {
+    /// The [`crate::TrackableResource::VERSION`] of the data. Older versions are migrated when they are pulled.
+    pub version: u32,
+    pub clock: VectorClock,
+    /// When the value was last changed, in milliseconds since the unix epoch
+    pub modified: u64,
+    pub data: Value,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PushResult {
+    Accepted,
+    /// The server has a value that the pushed value was not based on
+    Conflict(SyncedValue),
+}
+
+/// A server that tracked resources are synced with
+pub trait SyncTransport: Send + Sync + 'static {
+    /// Get the value stored on the server under `key`, or `None` if there is none
+    fn pull(&self, key: &str) -> Result<Option<SyncedValue>, SyncError>;
+
+    /// Store a value on the server.
+    /// The server must reject it with [`PushResult::Conflict`] unless the clock of its value is equal to or before the pushed clock.
+    fn push(&self, key: &str, value: &SyncedValue) -> Result<PushResult, SyncError>;
+}
+
+#[derive(Debug)]
+pub enum SyncError {
+    /// The server could not be reached
+    Transport(String),
+    /// The server returned an error status
+    Status(u16),
+    /// The server returned something that is not a [`SyncedValue`]
+    Response(String),
+    Serialize(serde_json::Error),
+    /// The remote value could not be migrated or deserialized
+    Load(LoadError),
+    /// The remote value is not valid and could not be repaired
+    Invalid(ValidationError),
+    /// Other devices kept pushing while conflicts were being resolved
+    TooManyConflicts,
+}
+
+impl Display for SyncError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SyncError::Transport(message) => write!(f, "Could not reach sync server: {message}"),
+            SyncError::Status(status) => write!(f, "Sync server returned status {status}"),
+            SyncError::Response(message) => {
+                write!(f, "Invalid response from sync server: {message}")
+            }
+            SyncError::Serialize(e) => e.fmt(f),
+            SyncError::Load(e) => e.fmt(f),
+            SyncError::Invalid(e) => e.fmt(f),
+            SyncError::TooManyConflicts => write!(f, "Gave up resolving conflicts"),
+        }
+    }
+}
+
+impl std::error::Error for SyncError {}
+
+/// How to resolve a value that was changed both locally and on another device since they were last synced
+pub enum ConflictStrategy<T> {
+    /// Keep whichever value was changed most recently
+    LastWriteWins,
+    /// Keep the local value
+    PreferLocal,
+    /// Combine the local and remote values
+    Merge(fn(local: &T, remote: &T) -> T),
+}
+
+impl<T> std::fmt::Debug for ConflictStrategy<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConflictStrategy::LastWriteWins => write!(f, "LastWriteWins"),
+            ConflictStrategy::PreferLocal => write!(f, "PreferLocal"),
+            ConflictStrategy::Merge(_) => write!(f, "Merge"),
+        }
+    }
+}
+
+impl<T> Clone for ConflictStrategy<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for ConflictStrategy<T> {}
+
+/// The sync server and the name of this device.
+/// Insert this to sync the resources added with [`crate::CanSyncTrackedResource::sync_tracked_resource`].
+///
+/// The device name must be unique to this device and must not change, for example a random id kept in a tracked resource.
+#[derive(Resource, Clone)]
+pub struct TrackedSync {
+    transport: Arc<dyn SyncTransport>,
+    device: String,
+}
+
+impl TrackedSync {
+    pub fn new(device: impl Into<String>, transport: impl SyncTransport) -> Self {
+        Self {
+            transport: Arc::new(transport),
+            device: device.into(),
+        }
+    }
+
+    pub fn device(&self) -> &str {
+        &self.device
+    }
+}
+
+/// Sync a tracked resource with the server now.
+/// Resources are also synced when the app starts and whenever they change.
+#[derive(Debug, Event)]
+pub struct SyncTrackedResource<R>(PhantomData<R>);
+
+impl<R> Default for SyncTrackedResource<R> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+/// Sent whenever a tracked resource has been synced
+#[derive(Debug, Clone, PartialEq, Eq, Event)]
+pub struct TrackedResourceSynced {
+    pub key: String,
+    pub outcome: SyncOutcome,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncOutcome {
+    /// The local and remote values were already the same
+    UpToDate,
+    /// The local value was newer and was pushed
+    Pushed,
+    /// The remote value was newer and replaced the local value
+    Pulled,
+    /// Both values had changed. The conflict was resolved and the result was pushed.
+    Resolved,
+}
+
+/// The sync state kept in storage alongside a value
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct SyncMetadata {
+    clock: VectorClock,
+    modified: u64,
+}
+
+struct Synced<R> {
+    outcome: SyncOutcome,
+    /// The value to use locally if it is not the local value
+    value: Option<R>,
+    metadata: SyncMetadata,
+}
+
+type SyncResult<R> = Arc<Mutex<Option<Result<Synced<R>, SyncError>>>>;
+
+/// A sync happening on the [`IoTaskPool`]
+struct SyncTask<R> {
+    /// The local clock when the sync started
+    clock: VectorClock,
+    result: SyncResult<R>,
+}
+
+#[derive(Resource)]
+struct SyncState<R> {
+    /// The storage key the metadata belongs to
+    key: Option<String>,
+    metadata: SyncMetadata,
+    requested: bool,
+    task: Option<SyncTask<R>>,
+}
+
+impl<R> Default for SyncState<R> {
+    fn default() -> Self {
+        Self {
+            key: None,
+            metadata: SyncMetadata::default(),
+            requested: false,
+            task: None,
+        }
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|x| x.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+fn lock<R>(result: &SyncResult<R>) -> MutexGuard<'_, Option<Result<Synced<R>, SyncError>>> {
+    result.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn to_synced<T: TrackableResource>(
+    value: &T,
+    metadata: &SyncMetadata,
+) -> Result<SyncedValue, SyncError> {
+    Ok(SyncedValue {
+        version: T::VERSION,
+        clock: metadata.clock.clone(),
+        modified: metadata.modified,
+        data: serde_json::to_value(&*value.before_save()).map_err(SyncError::Serialize)?,
+    })
+}
+
+fn from_synced<T: TrackableResource>(remote: &SyncedValue) -> Result<T, SyncError> {
+    let mut value: T =
+        load_versioned(json!({ "version": remote.version, "data": remote.data.clone() }))
+            .map_err(SyncError::Load)?;
+    if value.validate().is_err() {
+        value.repair();
+        value.validate().map_err(SyncError::Invalid)?;
+    }
+    Ok(value)
+}
+
+/// Pull the remote value, resolve it with the local value and push the result if the server needs it
+fn sync_value<T: TrackableResource>(
+    transport: &dyn SyncTransport,
+    key: &str,
+    device: &str,
+    mut local: T,
+    mut metadata: SyncMetadata,
+    now: u64,
+) -> Result<Synced<T>, SyncError> {
+    let mut remote = transport.pull(key)?;
+    let mut outcome = SyncOutcome::UpToDate;
+    let mut changed = false;
+
+    for _ in 0..MAX_ATTEMPTS {
+        if let Some(remote) = remote.take() {
+            match metadata.clock.compare(&remote.clock) {
+                ClockOrdering::Equal => {
+                    return Ok(Synced {
+                        outcome,
+                        value: changed.then_some(local),
+                        metadata,
+                    });
+                }
+                ClockOrdering::Before => {
+                    return Ok(Synced {
+                        outcome: SyncOutcome::Pulled,
+                        value: Some(from_synced(&remote)?),
+                        metadata: SyncMetadata {
+                            clock: remote.clock,
+                            modified: remote.modified,
+                        },
+                    });
+                }
+                ClockOrdering::After => {}
+                ClockOrdering::Concurrent => {
+                    let remote_value: T = from_synced(&remote)?;
+                    local = match T::CONFLICT_STRATEGY {
+                        ConflictStrategy::LastWriteWins if remote.modified > metadata.modified => {
+                            remote_value
+                        }
+                        ConflictStrategy::LastWriteWins | ConflictStrategy::PreferLocal => local,
+                        ConflictStrategy::Merge(merge) => merge(&local, &remote_value),
+                    };
+                    // The resolved value is a new change based on both values
+                    metadata.clock.merge(&remote.clock);
+                    metadata.clock.increment(device);
+                    metadata.modified = now.max(remote.modified);
+                    outcome = SyncOutcome::Resolved;
+                    changed = true;
+                }
+            }
+        }
+
+        if outcome == SyncOutcome::UpToDate {
+            outcome = SyncOutcome::Pushed;
+        }
+        match transport.push(key, &to_synced(&local, &metadata)?)? {
+            PushResult::Accepted => {
+                return Ok(Synced {
+                    outcome,
+                    value: changed.then_some(local),
+                    metadata,
+                });
+            }
+            PushResult::Conflict(current) => remote = Some(current),
+        }
+    }
+
+    Err(SyncError::TooManyConflicts)
+}
+
+fn save_metadata<R: TrackableResource>(
+    persistence: &mut Persistence<R>,
+    key: &str,
+    metadata: &SyncMetadata,
+) {
+    let result = serde_json::to_vec(metadata)
+        .map_err(PersistenceError::Serialize)
+        .and_then(|bytes| {
+            persistence
+                .storage
+                .set(&sync_key(key), &bytes)
+                .map_err(PersistenceError::Storage)
+        });
+    if let Err(error) = result {
+        persistence.report_error(sync_key(key), TrackedOperation::Save, error);
+    }
+}
+
+fn sync_changes<R: TrackableResource>(
+    mut requests: EventReader<SyncTrackedResource<R>>,
+    mut synced: EventWriter<TrackedResourceSynced>,
+    mut data: ResMut<R>,
+    mut state: ResMut<SyncState<R>>,
+    sync: Option<Res<TrackedSync>>,
+    mut persistence: Persistence<R>,
+) {
+    let requested = requests.read().count() > 0;
+    let Some(sync) = sync else {
+        return;
+    };
+    // Values that are not being saved are not synced either
+    if !persistence.mode.can_read() || !persistence.mode.can_write() {
+        return;
+    }
+    let state = state.as_mut();
+    let key = persistence.state.slot.key(R::KEY);
+
+    if state.key.as_ref() != Some(&key) {
+        // The app has just started or the slot has changed, so the value was loaded rather than changed
+        state.metadata = match persistence.storage.get(&sync_key(&key)) {
+            Ok(bytes) => bytes
+                .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+                .unwrap_or_default(),
+            Err(error) => {
+                persistence.report_error(sync_key(&key), TrackedOperation::Load, error.into());
+                SyncMetadata::default()
+            }
+        };
+        state.key = Some(key.clone());
+        state.task = None;
+        state.requested = true;
+    } else if data.is_changed() {
+        // Changes made by this system are not seen here next time, so pulled values are not counted as local changes
+        state.metadata.clock.increment(sync.device());
+        state.metadata.modified = now();
+        save_metadata(&mut persistence, &key, &state.metadata);
+        state.requested = true;
+    }
+
+    if let Some(task) = state.task.as_ref() {
+        let Some(result) = lock(&task.result).take() else {
+            return;
+        };
+        let task = state.task.take().expect("Task was checked above");
+
+        match result {
+            Ok(result) if task.clock != state.metadata.clock => {
+                // The value changed while it was being synced, so the result is out of date.
+                // The remote clock is not merged so the next sync still sees any remote changes as a conflict.
+                // This device's count must pass any change the sync pushed, so the local change is not mistaken for it.
+                while state.metadata.clock.get(sync.device())
+                    <= result.metadata.clock.get(sync.device())
+                {
+                    state.metadata.clock.increment(sync.device());
+                }
+                save_metadata(&mut persistence, &key, &state.metadata);
+                state.requested = true;
+            }
+            Ok(result) => {
+                if result.metadata != state.metadata {
+                    state.metadata = result.metadata;
+                    save_metadata(&mut persistence, &key, &state.metadata);
+                }
+                if let Some(mut value) = result.value {
+                    value.on_loaded();
+                    *data = value;
+                }
+                synced.send(TrackedResourceSynced {
+                    key: key.clone(),
+                    outcome: result.outcome,
+                });
+            }
+            Err(error) => {
+                // Try again when the value next changes or a sync is requested, rather than every frame
+                state.requested = false;
+                persistence.report_error(
+                    key,
+                    TrackedOperation::Sync,
+                    PersistenceError::Sync(error),
+                );
+                return;
+            }
+        }
+    }
+
+    if !state.requested && !requested {
+        return;
+    }
+    state.requested = false;
+
+    let result = SyncResult::default();
+    state.task = Some(SyncTask {
+        clock: state.metadata.clock.clone(),
+        result: result.clone(),
+    });
+
+    let transport = sync.transport.clone();
+    let device = sync.device.clone();
+    let local = data.clone();
+    let metadata = state.metadata.clone();
+    let now = now();
+    IoTaskPool::get_or_init(TaskPool::new)
+        .spawn(async move {
+            let synced = sync_value(&*transport, &key, &device, local, metadata, now);
+            *lock(&result) = Some(synced);
+        })
+        .detach();
+}
+
+pub(crate) struct SyncPlugin<R>(PhantomData<R>);
+
+impl<R> Default for SyncPlugin<R> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<R: TrackableResource> Plugin for SyncPlugin<R> {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SyncState<R>>();
+        app.add_event::<SyncTrackedResource<R>>();
+        app.add_event::<TrackedResourceSynced>();
+        app.add_systems(Last, sync_changes::<R>);
+    }
+}
+
+/// Syncs with a server over HTTP using JSON. Requires the `sync_http` feature.
+///
+/// Each value is at `{url}/{key}` with the key percent-encoded, and is sent and received as a [`SyncedValue`].
+/// - `GET` returns the value, or `404 Not Found` if there is none
+/// - `PUT` stores the value, unless the clock of the stored value is not equal to or before the clock of the new value.
+///   Then it returns `409 Conflict` with the stored value instead.
+#[cfg(all(feature = "sync_http", not(target_arch = "wasm32")))]
+pub struct HttpTransport {
+    agent: ureq::Agent,
+    url: String,
+    headers: Vec<(String, String)>,
+}
+
+#[cfg(all(feature = "sync_http", not(target_arch = "wasm32")))]
+impl HttpTransport {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            agent: ureq::Agent::new(),
+            url: url.into().trim_end_matches('/').to_string(),
+            headers: vec![],
+        }
+    }
+
+    /// Send a header with every request, for example to authenticate
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    fn request(&self, method: &str, key: &str) -> ureq::Request {
+        let url = format!("{}/{}", self.url, super::storage::encode_file_name(key));
+        self.headers.iter().fold(
+            self.agent.request(method, &url),
+            |request, (name, value)| request.set(name, value),
+        )
+    }
+}
+
+#[cfg(all(feature = "sync_http", not(target_arch = "wasm32")))]
+impl From<ureq::Error> for SyncError {
+    fn from(value: ureq::Error) -> Self {
+        match value {
+            ureq::Error::Status(status, _) => Self::Status(status),
+            ureq::Error::Transport(transport) => Self::Transport(transport.to_string()),
+        }
+    }
+}
+
+#[cfg(all(feature = "sync_http", not(target_arch = "wasm32")))]
+fn read_response(response: ureq::Response) -> Result<SyncedValue, SyncError> {
+    response
+        .into_json()
+        .map_err(|e| SyncError::Response(e.to_string()))
+}
+
+#[cfg(all(feature = "sync_http", not(target_arch = "wasm32")))]
+impl SyncTransport for HttpTransport {
+    fn pull(&self, key: &str) -> Result<Option<SyncedValue>, SyncError> {
+        match self.request("GET", key).call() {
+            Ok(response) => read_response(response).map(Some),
+            Err(ureq::Error::Status(404, _)) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn push(&self, key: &str, value: &SyncedValue) -> Result<PushResult, SyncError> {
+        match self.request("PUT", key).send_json(value) {
+            Ok(_) => Ok(PushResult::Accepted),
+            Err(ureq::Error::Status(409, response)) => {
+                read_response(response).map(PushResult::Conflict)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use crate::{
+        tracked_resource::{
+            storage::{MemoryStorage, TrackedStorage},
+            test_utils::{tracked_app, Gate, Volume},
+        },
+        CanInitTrackedResource, CanSyncTrackedResource,
+    };
+
+    use super::*;
+
+    /// A stand-in for a sync server
+    #[derive(Debug, Default, Clone)]
+    struct Server(Arc<Mutex<BTreeMap<String, SyncedValue>>>);
+
+    impl Server {
+        fn values(&self) -> MutexGuard<'_, BTreeMap<String, SyncedValue>> {
+            self.0.lock().unwrap()
+        }
+    }
+
+    impl SyncTransport for Server {
+        fn pull(&self, key: &str) -> Result<Option<SyncedValue>, SyncError> {
+            Ok(self.values().get(key).cloned())
+        }
+
+        fn push(&self, key: &str, value: &SyncedValue) -> Result<PushResult, SyncError> {
+            let mut values = self.values();
+            match values.get(key) {
+                Some(current)
+                    if !matches!(
+                        current.clock.compare(&value.clock),
+                        ClockOrdering::Equal | ClockOrdering::Before
+                    ) =>
+                {
+                    Ok(PushResult::Conflict(current.clone()))
+                }
+                _ => {
+                    values.insert(key.to_string(), value.clone());
+                    Ok(PushResult::Accepted)
+                }
+            }
+        }
+    }
+
+    /// A transport that waits for a gate to open before each request
+    #[derive(Debug, Clone)]
+    struct Gated<T>(Gate, T);
+
+    impl<T: SyncTransport> SyncTransport for Gated<T> {
+        fn pull(&self, key: &str) -> Result<Option<SyncedValue>, SyncError> {
+            self.0.pass();
+            self.1.pull(key)
+        }
+
+        fn push(&self, key: &str, value: &SyncedValue) -> Result<PushResult, SyncError> {
+            self.0.pass();
+            self.1.push(key, value)
+        }
+    }
+
+    /// Serve a [`Server`] with the protocol used by [`HttpTransport`]
+    #[cfg(feature = "sync_http")]
+    fn serve(server: Server) -> String {
+        let http = tiny_http::Server::http("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", http.server_addr().to_ip().unwrap());
+
+        std::thread::spawn(move || {
+            for mut request in http.incoming_requests() {
+                let key = request.url().trim_start_matches('/').to_string();
+                let (status, body) = match request.method() {
+                    tiny_http::Method::Get => match server.pull(&key).unwrap() {
+                        Some(value) => (200, serde_json::to_string(&value).unwrap()),
+                        None => (404, String::new()),
+                    },
+                    tiny_http::Method::Put => {
+                        let value: SyncedValue =
+                            serde_json::from_reader(request.as_reader()).unwrap();
+                        match server.push(&key, &value).unwrap() {
+                            PushResult::Accepted => (204, String::new()),
+                            PushResult::Conflict(current) => {
+                                (409, serde_json::to_string(&current).unwrap())
+                            }
+                        }
+                    }
+                    _ => (405, String::new()),
+                };
+                let _ = request
+                    .respond(tiny_http::Response::from_string(body).with_status_code(status));
+            }
+        });
+
+        url
+    }
+
+    #[derive(Debug, Default, Clone, PartialEq, Resource, Serialize, Deserialize)]
+    struct Notes(BTreeSet<String>);
+
+    impl TrackableResource for Notes {
+        const KEY: &'static str = "my_game::Notes";
+        const CONFLICT_STRATEGY: ConflictStrategy<Self> =
+            ConflictStrategy::Merge(|local, remote| {
+                Notes(local.0.union(&remote.0).cloned().collect())
+            });
+    }
+
+    fn device(name: &str, transport: impl SyncTransport) -> App {
+        let mut app = tracked_app(TrackedStorage::new(MemoryStorage::default()), |app| {
+            app.insert_resource(TrackedSync::new(name, transport));
+            app.init_tracked_resource::<Notes>();
+            app.sync_tracked_resource::<Notes>();
+        });
+        settle(&mut app);
+        app
+    }
+
+    /// Update until the sync has finished
+    fn settle(app: &mut App) {
+        for _ in 0..1000 {
+            app.update();
+            let state = app.world().resource::<SyncState<Notes>>();
+            if state.task.is_none() && !state.requested {
+                app.update();
+                return;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        panic!("Sync did not finish");
+    }
+
+    fn add_note(app: &mut App, note: &str) {
+        app.world_mut()
+            .resource_mut::<Notes>()
+            .0
+            .insert(note.to_string());
+        settle(app);
+    }
+
+    fn notes(app: &App) -> Vec<&str> {
+        app.world()
+            .resource::<Notes>()
+            .0
+            .iter()
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// Sync notes between two devices. `server_key` is the key the server stores the notes under.
+    fn sync_devices(
+        phone: impl SyncTransport,
+        laptop: impl SyncTransport,
+        server: &Server,
+        server_key: &str,
+    ) {
+        let mut phone = device("phone", phone);
+        let mut laptop = device("laptop", laptop);
+
+        add_note(&mut phone, "milk");
+        laptop
+            .world_mut()
+            .send_event(SyncTrackedResource::<Notes>::default());
+        settle(&mut laptop);
+        assert_eq!(notes(&laptop), ["milk"]);
+        let stored = laptop.world().resource::<TrackedStorage>().get(Notes::KEY);
+        assert!(String::from_utf8(stored.unwrap().unwrap())
+            .unwrap()
+            .contains("milk"));
+
+        // Both devices change the notes before syncing
+        phone
+            .world_mut()
+            .resource_mut::<Notes>()
+            .0
+            .insert("eggs".to_string());
+        laptop
+            .world_mut()
+            .resource_mut::<Notes>()
+            .0
+            .insert("bread".to_string());
+        settle(&mut phone);
+        settle(&mut laptop);
+        phone
+            .world_mut()
+            .send_event(SyncTrackedResource::<Notes>::default());
+        settle(&mut phone);
+
+        assert_eq!(notes(&phone), ["bread", "eggs", "milk"]);
+        assert_eq!(notes(&laptop), ["bread", "eggs", "milk"]);
+
+        let clock = &server.values()[server_key].clock;
+        assert_eq!((clock.get("phone"), clock.get("laptop")), (2, 2));
+    }
+
+    #[test]
+    pub fn test_changes_are_synced_between_devices() {
+        let server = Server::default();
+        sync_devices(server.clone(), server.clone(), &server, Notes::KEY);
+    }
+
+    #[cfg(feature = "sync_http")]
+    #[test]
+    pub fn test_changes_are_synced_over_http() {
+        let server = Server::default();
+        let url = serve(server.clone());
+        // The key is percent-encoded in the URL
+        let key = "my_game%3A%3ANotes";
+
+        sync_devices(
+            HttpTransport::new(&url),
+            HttpTransport::new(&url),
+            &server,
+            key,
+        );
+    }
+
+    #[test]
+    pub fn test_changes_during_a_sync_are_not_lost() {
+        let server = Server::default();
+        let gate = Gate::default();
+        gate.open();
+        let mut phone = device("phone", server.clone());
+        let mut laptop = device("laptop", Gated(gate.clone(), server.clone()));
+        add_note(&mut phone, "milk");
+        add_note(&mut phone, "eggs");
+
+        // The laptop starts a sync, which will pull the eggs, and changes the notes while it is in flight
+        gate.close();
+        laptop
+            .world_mut()
+            .send_event(SyncTrackedResource::<Notes>::default());
+        laptop.update();
+        laptop
+            .world_mut()
+            .resource_mut::<Notes>()
+            .0
+            .insert("bread".to_string());
+        laptop.update();
+        assert!(laptop.world().resource::<SyncState<Notes>>().task.is_some());
+
+        gate.open();
+        settle(&mut laptop);
+
+        assert_eq!(notes(&laptop), ["bread", "eggs", "milk"]);
+        let stored = &server.values()[Notes::KEY];
+        assert_eq!(stored.data, json!(["bread", "eggs", "milk"]));
+    }
+
+    #[test]
+    pub fn test_vector_clock() {
+        let mut a = VectorClock::default();
+        let mut b = VectorClock::default();
+        assert_eq!(a.compare(&b), ClockOrdering::Equal);
+
+        a.increment("a");
+        assert_eq!(a.compare(&b), ClockOrdering::After);
+        assert_eq!(b.compare(&a), ClockOrdering::Before);
+
+        b.increment("b");
+        assert_eq!(a.compare(&b), ClockOrdering::Concurrent);
+
+        a.merge(&b);
+        assert_eq!(a.compare(&b), ClockOrdering::After);
+        assert_eq!((a.get("a"), a.get("b"), a.get("c")), (1, 1, 0));
+    }
+
+    #[test]
+    pub fn test_last_write_wins() {
+        let server = Server::default();
+        let mut remote_clock = VectorClock::default();
+        remote_clock.increment("laptop");
+        server.values().insert(
+            Volume::KEY.to_string(),
+            SyncedValue {
+                version: 0,
+                clock: remote_clock,
+                modified: 200,
+                data: json!(5),
+            },
+        );
+
+        let mut local_clock = VectorClock::default();
+        local_clock.increment("phone");
+        let older = SyncMetadata {
+            clock: local_clock.clone(),
+            modified: 100,
+        };
+        let synced = sync_value(&server, Volume::KEY, "phone", Volume(1), older, 300).unwrap();
+        assert_eq!(synced.outcome, SyncOutcome::Resolved);
+        assert_eq!(synced.value, Some(Volume(5)));
+        assert_eq!(synced.metadata.clock.get("phone"), 2);
+        assert_eq!(server.values()[Volume::KEY].data, json!(5));
+
+        // Now the phone changes the value again, after the resolved value
+        let mut newer = synced.metadata;
+        newer.clock.increment("phone");
+        newer.modified = 400;
+        let synced = sync_value(&server, Volume::KEY, "phone", Volume(2), newer, 400).unwrap();
+        assert_eq!(synced.outcome, SyncOutcome::Pushed);
+        assert_eq!(synced.value, None);
+        assert_eq!(server.values()[Volume::KEY].data, json!(2));
+    }
+}
@@ -0,0 +1,215 @@
+use std::{collections::VecDeque, marker::PhantomData};
+
+use bevy::prelude::*;
+
+use super::save_policy::SaveTrackedResources;
+
+/// Restore the previous value of an undoable resource
+#[derive(Debug, Event)]
+pub struct Undo<R>(PhantomData<R>);
+
+/// Restore the value of an undoable resource from before the last undo
+#[derive(Debug, Event)]
+pub struct Redo<R>(PhantomData<R>);
+
+impl<R> Default for Undo<R> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<R> Default for Redo<R> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+/// The undo history of a resource. A snapshot of the resource is recorded whenever it changes.
+///
+/// Undoing or redoing changes the resource like any other change, so tracked resources save the restored value.
+#[derive(Debug, Resource)]
+pub struct UndoableResource<R> {
+    past: VecDeque<R>,
+    future: Vec<R>,
+    /// The value the resource had when it was last checked
+    current: Option<R>,
+    depth: usize,
+}
+
+impl<R> UndoableResource<R> {
+    pub fn new(depth: usize) -> Self {
+        Self {
+            past: VecDeque::new(),
+            future: vec![],
+            current: None,
+            depth,
+        }
+    }
+
+    /// The maximum number of changes that can be undone
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.past.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.future.is_empty()
+    }
+
+    /// The number of changes that can be undone
+    pub fn undo_len(&self) -> usize {
+        self.past.len()
+    }
+
+    /// The number of undone changes that can be redone
+    pub fn redo_len(&self) -> usize {
+        self.future.len()
+    }
+
+    /// Forget every recorded change
+    pub fn clear(&mut self) {
+        self.past.clear();
+        self.future.clear();
+    }
+}
+
+impl<R: Clone> UndoableResource<R> {
+    fn record(&mut self, value: &R) {
+        if let Some(previous) = self.current.replace(value.clone()) {
+            self.past.push_back(previous);
+            while self.past.len() > self.depth {
+                self.past.pop_front();
+            }
+            self.future.clear();
+        }
+    }
+
+    fn undo(&mut self) -> Option<R> {
+        let previous = self.past.pop_back()?;
+        if let Some(current) = self.current.replace(previous.clone()) {
+            self.future.push(current);
+        }
+        Some(previous)
+    }
+
+    fn redo(&mut self) -> Option<R> {
+        let next = self.future.pop()?;
+        if let Some(current) = self.current.replace(next.clone()) {
+            self.past.push_back(current);
+        }
+        Some(next)
+    }
+}
+
+pub(crate) struct UndoPlugin<R> {
+    depth: usize,
+    phantom: PhantomData<R>,
+}
+
+impl<R> UndoPlugin<R> {
+    pub fn new(depth: usize) -> Self {
+        Self {
+            depth,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<R: Resource + Clone> Plugin for UndoPlugin<R> {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(UndoableResource::<R>::new(self.depth));
+        app.add_event::<Undo<R>>();
+        app.add_event::<Redo<R>>();
+        app.add_systems(PostUpdate, undo_changes::<R>.before(SaveTrackedResources));
+    }
+}
+
+fn undo_changes<R: Resource + Clone>(
+    mut undo: EventReader<Undo<R>>,
+    mut redo: EventReader<Redo<R>>,
+    data: Option<ResMut<R>>,
+    mut history: ResMut<UndoableResource<R>>,
+) {
+    let Some(mut data) = data else {
+        return;
+    };
+
+    // Changes made by this system are not seen here next time, so undoing is not recorded as a change
+    if data.is_changed() {
+        history.record(&data);
+    }
+
+    for _ in undo.read() {
+        if let Some(previous) = history.undo() {
+            *data = previous;
+        }
+    }
+
+    for _ in redo.read() {
+        if let Some(next) = history.redo() {
+            *data = next;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        tracked_resource::{
+            migration::from_bytes,
+            storage::{MemoryStorage, TrackedStorage},
+            test_utils::{tracked_app, Volume},
+        },
+        CanInitTrackedResource, CanMakeUndoable, TrackableResource,
+    };
+
+    use super::*;
+
+    fn set_volume(app: &mut App, volume: u32) {
+        app.world_mut().resource_mut::<Volume>().0 = volume;
+        app.update();
+    }
+
+    fn volume(app: &App) -> (u32, u32) {
+        let storage = app.world().resource::<TrackedStorage>();
+        let stored = from_bytes::<Volume>(&storage.get(Volume::KEY).unwrap().unwrap()).unwrap();
+        (app.world().resource::<Volume>().0, stored.0)
+    }
+
+    #[test]
+    pub fn test_undo_and_redo_are_persisted() {
+        let mut app = tracked_app(TrackedStorage::new(MemoryStorage::default()), |app| {
+            app.make_undoable::<Volume>(2);
+            app.init_tracked_resource::<Volume>();
+        });
+        app.update();
+
+        for volume in 1..=4 {
+            set_volume(&mut app, volume);
+        }
+        assert_eq!(
+            app.world()
+                .resource::<UndoableResource<Volume>>()
+                .undo_len(),
+            2
+        );
+
+        for _ in 0..3 {
+            app.world_mut().send_event(Undo::<Volume>::default());
+        }
+        app.update();
+        assert_eq!(volume(&app), (2, 2));
+
+        app.world_mut().send_event(Redo::<Volume>::default());
+        app.update();
+        assert_eq!(volume(&app), (3, 3));
+
+        set_volume(&mut app, 10);
+        let history = app.world().resource::<UndoableResource<Volume>>();
+        assert!(!history.can_redo());
+        assert_eq!(history.undo_len(), 2);
+    }
+}